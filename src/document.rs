@@ -0,0 +1,454 @@
+use std::{fmt::Display, ops::Range};
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    error::Utf16,
+    layout::{self, Layout},
+    parser::MifHeader,
+    word::{self, Word},
+    writer, Radix,
+};
+
+/// Where a comment sits relative to the entry it annotates.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Placement {
+    /// On the lines right before the entry.
+    Leading = 0,
+    /// After the entry, on the same line.
+    Trailing = 1,
+}
+
+/// A comment next to a data entry, such as the label or source line an
+/// assembler writes there, with the addresses the entry assigns.
+#[wasm_bindgen]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Annotation {
+    addresses: Range<usize>,
+    text: String,
+    placement: Placement,
+}
+
+impl Annotation {
+    pub(crate) fn new(addresses: Range<usize>, text: &str, placement: Placement) -> Self {
+        Self {
+            addresses,
+            text: text.to_string(),
+            placement,
+        }
+    }
+
+    pub fn addresses(&self) -> Range<usize> {
+        self.addresses.clone()
+    }
+}
+
+#[wasm_bindgen]
+impl Annotation {
+    #[wasm_bindgen(getter)]
+    pub fn start(&self) -> usize {
+        self.addresses.start
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn end(&self) -> usize {
+        self.addresses.end
+    }
+
+    /// The comment without its delimiters or surrounding whitespace.
+    #[wasm_bindgen(getter)]
+    pub fn text(&self) -> String {
+        self.text.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn placement(&self) -> Placement {
+        self.placement
+    }
+}
+
+/// The data entry that assigned a run of addresses, as the byte range
+/// `span` of the input, or the range of UTF-16 code units from the wasm
+/// `parseMifRecovering`. `line` and `column` locate its start like in
+/// [`crate::MifError`].
+#[wasm_bindgen]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SourceSpan {
+    addresses: Range<usize>,
+    span: Range<usize>,
+    line: usize,
+    column: usize,
+}
+
+impl SourceSpan {
+    pub(crate) fn new(
+        addresses: Range<usize>,
+        span: Range<usize>,
+        line: usize,
+        column: usize,
+    ) -> Self {
+        Self {
+            addresses,
+            span,
+            line,
+            column,
+        }
+    }
+
+    pub fn addresses(&self) -> Range<usize> {
+        self.addresses.clone()
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// The span with its offsets in UTF-16 code units.
+    pub(crate) fn to_utf16(&self, offsets: &Utf16) -> Self {
+        Self {
+            span: offsets.span(self.span()),
+            ..self.clone()
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl SourceSpan {
+    #[wasm_bindgen(getter)]
+    pub fn start(&self) -> usize {
+        self.addresses.start
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn end(&self) -> usize {
+        self.addresses.end
+    }
+
+    #[wasm_bindgen(getter, js_name = "spanStart")]
+    pub fn span_start(&self) -> usize {
+        self.span.start
+    }
+
+    #[wasm_bindgen(getter, js_name = "spanEnd")]
+    pub fn span_end(&self) -> usize {
+        self.span.end
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+/// A parsed MIF file: its header attributes plus one word per address.
+///
+/// Words of any `width` are kept as 64-bit limbs, masked to `width` bits,
+/// and addresses never assigned in `CONTENT` read as zero. Which addresses
+/// were assigned is kept in a bitmap, one bit per address. Comments next to
+/// entries are kept as [`Annotation`]s, in file order. When asked for, the
+/// entry that set each address is kept as a [`SourceSpan`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MifDocument {
+    pub(crate) header: MifHeader,
+    pub(crate) words: Vec<u64>,
+    pub(crate) initialized: Vec<u64>,
+    pub(crate) annotations: Vec<Annotation>,
+    pub(crate) source_map: Vec<SourceSpan>,
+}
+
+impl MifDocument {
+    pub(crate) fn new(header: MifHeader) -> Self {
+        Self {
+            header,
+            words: vec![0; header.depth * word::limbs(header.width)],
+            initialized: vec![0; header.depth.div_ceil(64)],
+            annotations: Vec::new(),
+            source_map: Vec::new(),
+        }
+    }
+
+    pub fn header(&self) -> MifHeader {
+        self.header
+    }
+
+    pub fn width(&self) -> usize {
+        self.header.width
+    }
+
+    pub fn depth(&self) -> usize {
+        self.header.depth
+    }
+
+    pub fn address_radix(&self) -> Radix {
+        self.header.address_radix
+    }
+
+    pub fn data_radix(&self) -> Radix {
+        self.header.data_radix
+    }
+
+    /// Returns the word at `address`, or `None` if it is past `DEPTH`.
+    pub fn get(&self, address: usize) -> Option<Word<'_>> {
+        let limbs = word::limbs(self.header.width);
+        let start = address.checked_mul(limbs)?;
+
+        self.words
+            .get(start..start.checked_add(limbs)?)
+            .map(|limbs| Word::new(limbs, self.header.width))
+    }
+
+    pub(crate) fn set(&mut self, address: usize, value: Word) {
+        let limbs = word::limbs(self.header.width);
+
+        self.words[address * limbs..][..limbs].copy_from_slice(value.limbs());
+        self.initialized[address / 64] |= 1 << (address % 64);
+    }
+
+    /// Writes the words of `pattern`, repeated from address 0, to every
+    /// address that wasn't initialized. See [`crate::ParseOptions::fill`].
+    pub(crate) fn fill(&mut self, pattern: &[Vec<u64>]) {
+        if pattern.is_empty() {
+            return;
+        }
+
+        let width = self.header.width;
+        let limbs = word::limbs(width);
+        let mut from = 0;
+
+        while let Some(start) = self.next(from, false) {
+            let end = self.next(start, true).unwrap_or(self.header.depth);
+
+            for address in start..end {
+                let word = &mut self.words[address * limbs..][..limbs];
+                let fill = &pattern[address % pattern.len()];
+                let length = fill.len().min(limbs);
+
+                word.fill(0);
+                word[..length].copy_from_slice(&fill[..length]);
+                word::mask(word, width);
+            }
+
+            from = end;
+        }
+    }
+
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    /// The annotations of every entry assigning `address`.
+    pub fn annotations_at(&self, address: usize) -> impl Iterator<Item = &Annotation> + '_ {
+        self.annotations
+            .iter()
+            .filter(move |annotation| annotation.addresses.contains(&address))
+    }
+
+    /// The entries that set each run of addresses, in ascending address
+    /// order. Empty unless [`crate::ParseOptions::source_map`] was set.
+    pub fn source_map(&self) -> &[SourceSpan] {
+        &self.source_map
+    }
+
+    /// The entry that set `address`, if it is in the source map.
+    pub fn source_of(&self, address: usize) -> Option<&SourceSpan> {
+        let index = self
+            .source_map
+            .partition_point(|source| source.addresses.end <= address);
+
+        self.source_map
+            .get(index)
+            .filter(|source| source.addresses.contains(&address))
+    }
+
+    /// Whether an entry of `CONTENT` assigned `address`.
+    pub fn is_initialized(&self, address: usize) -> bool {
+        address < self.header.depth && self.initialized[address / 64] >> (address % 64) & 1 == 1
+    }
+
+    /// The runs of addresses no entry of `CONTENT` assigned, in ascending
+    /// order.
+    pub fn uninitialized(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        let mut address = 0;
+
+        std::iter::from_fn(move || {
+            let start = self.next(address, false)?;
+            let end = self.next(start, true).unwrap_or(self.header.depth);
+
+            address = end;
+            Some(start..end)
+        })
+    }
+
+    /// The first address from `from` on whose initialized bit is `set`.
+    fn next(&self, from: usize, set: bool) -> Option<usize> {
+        let flip = match set {
+            true => 0,
+            false => u64::MAX,
+        };
+        let mut index = from / 64;
+        let mut bits = (self.initialized.get(index)? ^ flip) & u64::MAX << (from % 64);
+
+        while bits == 0 {
+            index += 1;
+            bits = self.initialized.get(index)? ^ flip;
+        }
+
+        Some(index * 64 + bits.trailing_zeros() as usize)
+            .filter(|&address| address < self.header.depth)
+    }
+
+    /// Iterates over every word, from address 0 up to `DEPTH - 1`.
+    pub fn words(&self) -> impl ExactSizeIterator<Item = Word<'_>> + '_ {
+        self.words
+            .chunks_exact(word::limbs(self.header.width))
+            .map(|limbs| Word::new(limbs, self.header.width))
+    }
+
+    /// Packs the words into a byte buffer as described by `layout`.
+    pub fn to_bytes(&self, layout: Layout) -> Vec<u8> {
+        layout::pack(self.words(), self.header.width, layout)
+    }
+}
+
+impl Display for MifDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let words = self.words().collect::<Vec<_>>();
+
+        writer::Mif::new(&words, self.header.address_radix, self.header.data_radix)
+            .with_width(self.header.width)
+            .fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::parse;
+
+    use super::*;
+
+    #[test]
+    pub fn accessors() {
+        let mif = parse(
+            "WIDTH=12;\nDEPTH=8;\nADDRESS_RADIX=UNS;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n1: ABC;\n[2..3]: 1 2;\n7: FFFF;\nEND;",
+        )
+        .unwrap();
+
+        assert_eq!(mif.width(), 12);
+        assert_eq!(mif.depth(), 8);
+        assert_eq!(mif.address_radix(), Radix::Uns);
+        assert_eq!(mif.data_radix(), Radix::Hex);
+        assert_eq!(mif.get(1).and_then(|word| word.to_u64()), Some(0xABC));
+        assert_eq!(mif.get(7).and_then(|word| word.to_u64()), Some(0xFFF));
+        assert_eq!(mif.get(8), None);
+        assert_eq!(mif.get(usize::MAX), None);
+        assert!(mif.is_initialized(2));
+        assert!(!mif.is_initialized(4));
+        assert!(!mif.is_initialized(8));
+        assert_eq!(mif.uninitialized().collect::<Vec<_>>(), [0..1, 4..7]);
+        assert_eq!(
+            mif.words()
+                .map(|word| word.to_u64().unwrap())
+                .collect::<Vec<_>>(),
+            [0, 0xABC, 1, 2, 0, 0, 0, 0xFFF]
+        );
+    }
+
+    #[test]
+    pub fn round_trip() {
+        let mif = parse(
+            "WIDTH=16;\nDEPTH=4;\nADDRESS_RADIX=HEX;\nDATA_RADIX=BIN;\nCONTENT BEGIN\n0: 1;\n[1..3]: 101;\nEND;",
+        )
+        .unwrap();
+        let text = mif.to_string();
+
+        assert!(text.starts_with("DEPTH=4;\nWIDTH=16;\n"));
+        assert_eq!(parse(&text), Ok(mif));
+    }
+
+    #[test]
+    pub fn wide() {
+        let mif = parse(
+            "WIDTH=144;\nDEPTH=2;\nADDRESS_RADIX=UNS;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n0: ABCD0123456789ABCDEF0123456789ABCDEF;\n1: 1;\nEND;",
+        )
+        .unwrap();
+        let word = mif.get(0).unwrap();
+
+        assert_eq!(word.width(), 144);
+        assert_eq!(
+            word.limbs(),
+            [0x0123_4567_89AB_CDEF, 0x0123_4567_89AB_CDEF, 0xABCD]
+        );
+        assert_eq!(word.to_u128(), None);
+        assert_eq!(mif.get(1).unwrap().to_u128(), Some(1));
+        assert_eq!(parse(mif.to_string()), Ok(mif.clone()));
+
+        let text = format!(
+            "WIDTH=4096;\nDEPTH=2;\nADDRESS_RADIX=UNS;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n[0..1]: {};\nEND;",
+            "F".repeat(1024)
+        );
+        let mif = parse(&text).unwrap();
+
+        assert!(mif.words().all(|word| word.limbs() == [u64::MAX; 64]));
+        assert_eq!(parse(mif.to_string()), Ok(mif));
+    }
+
+    #[test]
+    pub fn uninitialized() {
+        let mif = parse(
+            "WIDTH=8;\nDEPTH=200;\nADDRESS_RADIX=UNS;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n[0..63]: 0;\n[64..130]: 0;\n199: 0;\nEND;",
+        )
+        .unwrap();
+
+        assert_eq!(mif.uninitialized().collect::<Vec<_>>(), vec![131..199]);
+
+        let mif = parse(
+            "WIDTH=8;\nDEPTH=70;\nADDRESS_RADIX=UNS;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n[1..68]: 0;\nEND;",
+        )
+        .unwrap();
+
+        assert_eq!(mif.uninitialized().collect::<Vec<_>>(), [0..1, 69..70]);
+    }
+
+    #[test]
+    pub fn annotations() {
+        let mif = parse(
+            "WIDTH=8;\nDEPTH=8;\nADDRESS_RADIX=UNS;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n-- start:\n0: 1 2; -- ld a, 2\n% loop: %\n[2..5]: 0;  % nop %\n-- not attached\n6: 1;\n7: 1;\n-- end\nEND;",
+        )
+        .unwrap();
+        let annotations = mif
+            .annotations()
+            .iter()
+            .map(|annotation| {
+                (
+                    annotation.addresses(),
+                    annotation.text(),
+                    annotation.placement(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            annotations,
+            [
+                (0..2, "start:".to_string(), Placement::Leading),
+                (0..2, "ld a, 2".to_string(), Placement::Trailing),
+                (2..6, "loop:".to_string(), Placement::Leading),
+                (2..6, "nop".to_string(), Placement::Trailing),
+                (6..7, "not attached".to_string(), Placement::Leading),
+            ]
+        );
+        assert_eq!(
+            mif.annotations_at(3)
+                .map(|annotation| annotation.text())
+                .collect::<Vec<_>>(),
+            ["loop:", "nop"]
+        );
+        assert_eq!(mif.annotations_at(7).count(), 0);
+    }
+}
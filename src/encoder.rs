@@ -0,0 +1,429 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::parser::Address;
+
+/// Repeating patterns longer than this aren't looked for.
+const MAX_PERIOD: usize = 16;
+
+/// How many of the most common values are tried as background fill.
+const BACKGROUNDS: usize = 2;
+
+/// Runs of at least this many addresses without a value of their own split
+/// the words into parts planned on their own.
+pub(crate) const GAP: usize = 64;
+
+/// Lengths of the pieces of text an entry is made of.
+pub(crate) struct Costs<'a> {
+    /// The text of each value, by id.
+    pub values: &'a [usize],
+    /// The text of an address.
+    pub address: &'a dyn Fn(usize) -> usize,
+    /// Everything in an entry besides its addresses and values.
+    pub entry: usize,
+    /// The brackets and `..` of a range.
+    pub range: usize,
+    /// Between two values.
+    pub separator: usize,
+}
+
+impl Costs<'_> {
+    /// The text `entries` take.
+    pub(crate) fn of(&self, entries: &[(Address, Vec<usize>)]) -> usize {
+        entries
+            .iter()
+            .map(|(address, values)| {
+                let range = match address.from == address.to {
+                    true => 0,
+                    false => self.range + (self.address)(address.to),
+                };
+
+                self.entry
+                    + (self.address)(address.from)
+                    + range
+                    + values.iter().map(|&id| self.values[id]).sum::<usize>()
+                    + (values.len() - 1) * self.separator
+            })
+            .sum()
+    }
+}
+
+/// Finds the entries writing `words` in the least text. `words[i]` is the
+/// id of the value at address `start + i`, or `None` if the address must
+/// either be left out or hold `zero`.
+///
+/// An entry is a run of consecutive values or a range repeating a pattern of
+/// values, ending anywhere before the pattern stops repeating. With `overrides`, the first entry may also be a range covering
+/// every address, which later entries override.
+///
+/// Time and memory are linear in the number of words.
+pub(crate) fn smallest(
+    words: &[Option<usize>],
+    start: usize,
+    zero: usize,
+    costs: &Costs,
+    overrides: bool,
+) -> Vec<(Address, Vec<usize>)> {
+    let address = |i: usize| (costs.address)(start + i);
+    let costs = &Costs {
+        address: &address,
+        ..*costs
+    };
+    let target = words
+        .iter()
+        .map(|word| word.unwrap_or(zero))
+        .collect::<Vec<_>>();
+    let mut counts = HashMap::new();
+
+    for &id in &target {
+        *counts.entry(id).or_insert(0usize) += 1;
+    }
+
+    let mut common = counts.into_iter().collect::<Vec<_>>();
+    common.sort_by_key(|&(id, count)| (usize::MAX - count, id));
+
+    let backgrounds = match (overrides, target.len()) {
+        (false, _) | (_, 0 | 1) => Vec::new(),
+        _ => common
+            .into_iter()
+            .take(BACKGROUNDS)
+            .map(|(id, _)| id)
+            .collect(),
+    };
+
+    std::iter::once(None)
+        .chain(backgrounds.into_iter().map(Some))
+        .map(|background| plan(words, &target, background, costs))
+        .min_by_key(|(cost, _)| *cost)
+        .map_or(Vec::new(), |(_, entries)| entries)
+        .into_iter()
+        .map(|(Address { from, to }, values)| {
+            (
+                Address {
+                    from: start + from,
+                    to: start + to,
+                },
+                values,
+            )
+        })
+        .collect()
+}
+
+/// How the cheapest way to write the addresses before `i` ends.
+#[derive(Clone, Copy)]
+enum Step {
+    None,
+    /// Address `i - 1` needs no entry.
+    Skip,
+    /// A run of consecutive values ends at `i`.
+    Close,
+    /// A range repeating `period` values starts at `start`.
+    Range {
+        start: usize,
+        period: usize,
+    },
+    /// A run of consecutive values starts at `i - 1`.
+    Open,
+    /// A run of consecutive values goes on with `i - 1`.
+    Extend,
+}
+
+/// The cheapest entries for `target`, written over `background` if there is
+/// one, with their total cost.
+fn plan(
+    words: &[Option<usize>],
+    target: &[usize],
+    background: Option<usize>,
+    costs: &Costs,
+) -> (usize, Vec<(Address, Vec<usize>)>) {
+    let depth = target.len();
+    let done = |i: usize| match background {
+        Some(background) => target[i] == background,
+        None => words[i].is_none(),
+    };
+    let pattern = |start: usize, period: usize| {
+        target[start..start + period]
+            .iter()
+            .map(|&id| costs.values[id])
+            .sum::<usize>()
+            + (period - 1) * costs.separator
+    };
+
+    // `closed[i]` writes every address before `i` with nothing left open,
+    // `open[i]` ends in a run of consecutive values that can go on.
+    let mut closed = vec![(usize::MAX, Step::None); depth + 1];
+    let mut open = vec![(usize::MAX, Step::None); depth + 1];
+    let relax = |slot: &mut (usize, Step), cost: usize, step: Step| {
+        if cost < slot.0 {
+            *slot = (cost, step);
+        }
+    };
+
+    closed[0].0 = background.map_or(0, |id| {
+        costs.entry
+            + costs.range
+            + (costs.address)(0)
+            + (costs.address)(depth - 1)
+            + costs.values[id]
+    });
+
+    // The first address from `i` on that breaks a repetition with each
+    // period, found again only once `i` has passed it.
+    let mut breaks = [0; MAX_PERIOD + 1];
+    // For each period, the ranges started so far as their start, their cost
+    // before the last address and the furthest they can end. `pending` ones
+    // are still too short to end at `i`, `ending` ones can and are kept in
+    // order of cost, dropping any that neither ends later nor costs less
+    // than a later one.
+    let mut pending = vec![VecDeque::new(); MAX_PERIOD + 1];
+    let mut ending = vec![VecDeque::<(usize, usize, usize)>::new(); MAX_PERIOD + 1];
+
+    for i in 0..=depth {
+        for period in 1..=MAX_PERIOD {
+            while let Some(&(start, cost, end)) = pending[period].front() {
+                if i - start <= period {
+                    break;
+                }
+
+                pending[period].pop_front();
+
+                while ending[period]
+                    .back()
+                    .is_some_and(|&(_, other, _)| other >= cost)
+                {
+                    ending[period].pop_back();
+                }
+
+                ending[period].push_back((start, cost, end));
+            }
+
+            while ending[period].front().is_some_and(|&(_, _, end)| end < i) {
+                ending[period].pop_front();
+            }
+
+            if let Some(&(start, cost, _)) = ending[period].front() {
+                relax(
+                    &mut closed[i],
+                    cost + (costs.address)(i - 1),
+                    Step::Range { start, period },
+                );
+            }
+        }
+
+        if open[i].0 < closed[i].0 {
+            closed[i] = (open[i].0, Step::Close);
+        }
+
+        if i == depth {
+            break;
+        }
+
+        let (cost, _) = closed[i];
+        let value = costs.values[target[i]];
+
+        if done(i) {
+            relax(&mut closed[i + 1], cost, Step::Skip);
+        }
+
+        relax(
+            &mut open[i + 1],
+            cost + costs.entry + (costs.address)(i) + value,
+            Step::Open,
+        );
+
+        if let (extended, false) = open[i].0.overflowing_add(costs.separator + value) {
+            relax(&mut open[i + 1], extended, Step::Extend);
+        }
+
+        for period in 1..=MAX_PERIOD.min(depth - i) {
+            let end = &mut breaks[period];
+
+            if *end < i {
+                *end = i;
+            }
+
+            while *end + period < depth && target[*end] == target[*end + period] {
+                *end += 1;
+            }
+
+            let end = (*end + period).min(depth);
+
+            if end - i > period {
+                pending[period].push_back((
+                    i,
+                    cost + costs.entry + costs.range + (costs.address)(i) + pattern(i, period),
+                    end,
+                ));
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    let mut values = Vec::new();
+    let mut i = depth;
+
+    while i > 0 {
+        match closed[i].1 {
+            Step::Skip => i -= 1,
+            Step::Range { start, period } => {
+                entries.push((
+                    Address {
+                        from: start,
+                        to: i - 1,
+                    },
+                    target[start..start + period].to_vec(),
+                ));
+                i = start;
+            }
+            Step::Close => loop {
+                values.push(target[i - 1]);
+                i -= 1;
+
+                if let Step::Open = open[i + 1].1 {
+                    values.reverse();
+                    entries.push((Address { from: i, to: i }, std::mem::take(&mut values)));
+                    break;
+                }
+            },
+            _ => unreachable!("every address is written or skipped"),
+        }
+    }
+
+    if let Some(id) = background {
+        entries.push((
+            Address {
+                from: 0,
+                to: depth - 1,
+            },
+            vec![id],
+        ));
+    }
+
+    entries.reverse();
+
+    (closed[depth].0, entries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const COSTS: Costs = Costs {
+        values: &[1; 10],
+        address: &|_| 2,
+        entry: 3,
+        range: 4,
+        separator: 1,
+    };
+
+    fn entries(words: &[Option<usize>]) -> Vec<(Address, Vec<usize>)> {
+        smallest(words, 0, 0, &COSTS, true)
+    }
+
+    fn at(from: usize, to: usize, values: &[usize]) -> (Address, Vec<usize>) {
+        (Address { from, to }, values.to_vec())
+    }
+
+    #[test]
+    pub fn plans() {
+        assert_eq!(entries(&[]), []);
+        assert_eq!(entries(&[None, None]), []);
+        assert_eq!(
+            entries(&[Some(1), Some(2), Some(3), None, Some(4)]),
+            [at(0, 0, &[1, 2, 3, 0, 4])]
+        );
+        assert_eq!(
+            entries(&[vec![Some(1), Some(2)], vec![None; 10], vec![Some(4)]].concat()),
+            [at(0, 0, &[1, 2]), at(12, 12, &[4])]
+        );
+        assert_eq!(
+            entries(&[Some(1), Some(2), Some(3)].repeat(10)),
+            [at(0, 29, &[1, 2, 3])]
+        );
+        assert_eq!(
+            entries(&[[Some(5)].repeat(40), vec![Some(6)], [Some(5)].repeat(40)].concat()),
+            [at(0, 80, &[5]), at(40, 40, &[6])]
+        );
+
+        let words = [[Some(5)].repeat(40), vec![Some(6)], [Some(5)].repeat(40)].concat();
+        let disjoint = smallest(&words, 100, 0, &COSTS, false);
+
+        assert_eq!(
+            disjoint,
+            [at(100, 139, &[5]), at(140, 140, &[6]), at(141, 180, &[5])]
+        );
+        assert_eq!(COSTS.of(&disjoint), 3 * (3 + 2 + 1) + 2 * (4 + 2));
+    }
+
+    #[test]
+    pub fn any_end() {
+        let mut seed = 0x9E37_79B9_7F4A_7C15u64;
+        let mut random = |bound: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % bound) as usize
+        };
+        let costs = Costs {
+            address: &|address| 1 + address / 8,
+            ..COSTS
+        };
+
+        // Stopping the first range one short of where its pattern breaks lets
+        // the second start at a shorter address.
+        let mut cases = vec![[1, 2, 1, 2, 1, 2, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2]
+            .map(Some)
+            .to_vec()];
+
+        for _ in 0..300 {
+            let words = (0..1 + random(24))
+                .map(|_| (random(5) > 0).then(|| random(3)))
+                .collect::<Vec<_>>();
+            cases.push(words);
+        }
+
+        for words in cases {
+            let target = words
+                .iter()
+                .map(|word| word.unwrap_or(0))
+                .collect::<Vec<_>>();
+            let values = |i: usize, j: usize| {
+                target[i..j]
+                    .iter()
+                    .map(|&id| costs.values[id])
+                    .sum::<usize>()
+                    + (j - i - 1) * costs.separator
+            };
+
+            // Every run and every range ending anywhere, tried one by one.
+            let mut best = vec![usize::MAX; words.len() + 1];
+            best[0] = 0;
+
+            for j in 1..=words.len() {
+                if words[j - 1].is_none() {
+                    best[j] = best[j - 1];
+                }
+
+                for i in 0..j {
+                    let run = best[i] + costs.entry + (costs.address)(i) + values(i, j);
+                    best[j] = best[j].min(run);
+
+                    for period in 1..=MAX_PERIOD.min(j - i - 1) {
+                        if (i..j - period).all(|k| target[k] == target[k + period]) {
+                            let range = best[i]
+                                + costs.entry
+                                + costs.range
+                                + (costs.address)(i)
+                                + (costs.address)(j - 1)
+                                + values(i, i + period);
+                            best[j] = best[j].min(range);
+                        }
+                    }
+                }
+            }
+
+            let entries = smallest(&words, 0, 0, &costs, false);
+
+            assert_eq!(costs.of(&entries), best[words.len()], "{words:?}");
+        }
+    }
+}
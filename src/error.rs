@@ -0,0 +1,373 @@
+use std::{fmt::Display, ops::Range};
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    parser::{Address, Header},
+    Radix,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorKind {
+    MissingHeader(Header),
+    InvalidDigit(Radix),
+    NumberOverflow,
+    ValueOutOfRange,
+    AddressOutOfRange,
+    ReversedRange,
+    UnexpectedToken,
+    TrailingInput,
+    MissingContent,
+    Overlap(Address),
+    Io(std::io::ErrorKind),
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingHeader(header) => write!(f, "missing {header} header"),
+            Self::InvalidDigit(radix) => write!(f, "invalid digit for {radix} radix"),
+            Self::NumberOverflow => f.write_str("number too large"),
+            Self::ValueOutOfRange => f.write_str("value does not fit in WIDTH"),
+            Self::AddressOutOfRange => f.write_str("address out of range"),
+            Self::ReversedRange => f.write_str("range starts after its end"),
+            Self::UnexpectedToken => f.write_str("unexpected token"),
+            Self::TrailingInput => f.write_str("unexpected input after END;"),
+            Self::MissingContent => f.write_str("missing CONTENT BEGIN"),
+            Self::Overlap(Address { from, to }) if from == to => {
+                write!(f, "address {from} assigned more than once")
+            }
+            Self::Overlap(Address { from, to }) => {
+                write!(f, "addresses {from} to {to} assigned more than once")
+            }
+            Self::Io(kind) => write!(f, "I/O error: {kind}"),
+        }
+    }
+}
+
+/// An error found while parsing a MIF file.
+///
+/// `offset` is a byte offset into the input, `line` and `column` are 1-based
+/// and `column` counts characters.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MifError {
+    kind: ErrorKind,
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl MifError {
+    pub(crate) fn new(kind: ErrorKind, source: &str, offset: usize) -> Self {
+        let (line, column) = location(source, offset);
+
+        Self::at(kind, offset, line, column)
+    }
+
+    pub(crate) fn at(kind: ErrorKind, offset: usize, line: usize, column: usize) -> Self {
+        Self {
+            kind,
+            offset,
+            line,
+            column,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl Display for MifError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.kind)
+    }
+}
+
+impl std::error::Error for MifError {}
+
+/// Line and column, both 1-based, of byte `offset` in `source`.
+///
+/// Lines end at `\n`, `\r` or `\r\n`, and a byte order mark at the start of
+/// `source` doesn't count as a column.
+pub(crate) fn location(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches(['\n', '\r']).count() - before.matches("\r\n").count() + 1;
+    let line_start = before.rfind(['\n', '\r']).map_or(0, |i| i + 1);
+    let column = before[line_start..]
+        .trim_start_matches('\u{FEFF}')
+        .chars()
+        .count()
+        + 1;
+
+    (line, column)
+}
+
+/// Where each line of a source starts, to find the line and column of many
+/// offsets without rescanning the source from the start for each.
+pub(crate) struct Lines<'a> {
+    source: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+    pub(crate) fn new(source: &'a str) -> Self {
+        let bytes = source.as_bytes();
+        let starts = std::iter::once(0)
+            .chain(bytes.iter().enumerate().filter_map(|(i, &byte)| {
+                let ends = byte == b'\n' || byte == b'\r' && bytes.get(i + 1) != Some(&b'\n');
+
+                ends.then_some(i + 1)
+            }))
+            .collect();
+
+        Self { source, starts }
+    }
+
+    /// Same as [`location`] in the source.
+    pub(crate) fn location(&self, offset: usize) -> (usize, usize) {
+        // Between the `\r` and `\n` of a line break the next line has
+        // already started.
+        let bytes = self.source.as_bytes();
+
+        if offset > 0 && bytes[offset - 1] == b'\r' && bytes.get(offset) == Some(&b'\n') {
+            return (self.starts.partition_point(|&start| start <= offset) + 1, 1);
+        }
+
+        let line = self.starts.partition_point(|&start| start <= offset);
+        let column = self.source[self.starts[line - 1]..offset]
+            .trim_start_matches('\u{FEFF}')
+            .chars()
+            .count()
+            + 1;
+
+        (line, column)
+    }
+}
+
+/// Converts byte offsets into a source to offsets in UTF-16 code units, the
+/// way JS indexes strings.
+pub(crate) struct Utf16 {
+    /// For each character that isn't ASCII, the byte offset right after it
+    /// and how many fewer code units than bytes the source has up to there.
+    shrinks: Vec<(usize, usize)>,
+}
+
+impl Utf16 {
+    pub(crate) fn new(source: &str) -> Self {
+        let mut shrink = 0;
+        let shrinks = source
+            .char_indices()
+            .filter(|(_, char)| !char.is_ascii())
+            .map(|(offset, char)| {
+                shrink += char.len_utf8() - char.len_utf16();
+                (offset + char.len_utf8(), shrink)
+            })
+            .collect();
+
+        Self { shrinks }
+    }
+
+    pub(crate) fn offset(&self, offset: usize) -> usize {
+        let index = self.shrinks.partition_point(|&(end, _)| end <= offset);
+
+        offset
+            - index
+                .checked_sub(1)
+                .map_or(0, |index| self.shrinks[index].1)
+    }
+
+    pub(crate) fn span(&self, span: Range<usize>) -> Range<usize> {
+        self.offset(span.start)..self.offset(span.end)
+    }
+}
+
+impl From<MifError> for JsValue {
+    fn from(error: MifError) -> Self {
+        JsError::new(&error.to_string()).into()
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Error = 0,
+    Warning = 1,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        })
+    }
+}
+
+/// A problem found while parsing, covering the byte range `start..end` of
+/// the input, or the range of UTF-16 code units from the wasm
+/// `parseMifRecovering`. `line` and `column` locate `start` like in
+/// [`MifError`].
+///
+/// Some problems also point at a related part of the input, such as the
+/// earlier entry an overlapping one conflicts with.
+#[wasm_bindgen]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Diagnostic {
+    severity: Severity,
+    kind: ErrorKind,
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+    related: Option<(Range<usize>, usize)>,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(
+        severity: Severity,
+        kind: ErrorKind,
+        lines: &Lines,
+        span: Range<usize>,
+    ) -> Self {
+        let (line, column) = lines.location(span.start);
+
+        Self {
+            severity,
+            kind,
+            start: span.start,
+            end: span.end,
+            line,
+            column,
+            related: None,
+        }
+    }
+
+    pub(crate) fn with_related(mut self, lines: &Lines, span: Range<usize>) -> Self {
+        let (line, _) = lines.location(span.start);
+
+        self.related = Some((span, line));
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn related(&self) -> Option<Range<usize>> {
+        self.related.as_ref().map(|(span, _)| span.clone())
+    }
+
+    pub fn to_error(&self) -> MifError {
+        MifError::at(self.kind, self.start, self.line, self.column)
+    }
+
+    /// The diagnostic with its offsets in UTF-16 code units.
+    pub(crate) fn to_utf16(&self, offsets: &Utf16) -> Self {
+        let span = offsets.span(self.span());
+
+        Self {
+            start: span.start,
+            end: span.end,
+            related: self
+                .related
+                .clone()
+                .map(|(span, line)| (offsets.span(span), line)),
+            ..self.clone()
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Diagnostic {
+    #[wasm_bindgen(getter)]
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    #[wasm_bindgen(getter, js_name = "relatedStart")]
+    pub fn related_start(&self) -> Option<usize> {
+        self.related.as_ref().map(|(span, _)| span.start)
+    }
+
+    #[wasm_bindgen(getter, js_name = "relatedEnd")]
+    pub fn related_end(&self) -> Option<usize> {
+        self.related.as_ref().map(|(span, _)| span.end)
+    }
+
+    #[wasm_bindgen(getter, js_name = "relatedLine")]
+    pub fn related_line(&self) -> Option<usize> {
+        self.related.as_ref().map(|(_, line)| *line)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.kind.to_string()
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line, self.column, self.severity, self.kind
+        )?;
+
+        match &self.related {
+            Some((_, line)) => write!(f, " (see line {line})"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn lines() {
+        let source = "\u{FEFF}a\r\nbé\rc\n\n\u{FEFF}d\r";
+        let lines = Lines::new(source);
+
+        for offset in (0..=source.len()).filter(|&i| source.is_char_boundary(i)) {
+            assert_eq!(lines.location(offset), location(source, offset), "{offset}");
+        }
+    }
+}
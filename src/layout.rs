@@ -0,0 +1,248 @@
+use wasm_bindgen::prelude::*;
+
+use crate::word::{self, Word};
+
+/// How words are laid out in the buffer produced from a parsed MIF.
+///
+/// The buffer is a bit stream where word `n` takes the `stride` bits starting
+/// at bit `n * stride`, with the word right-aligned in them and any padding
+/// bits zero. How stream bits map to bytes is set by [`Endianness`].
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Packing {
+    /// Words are stored back to back with no padding, `stride = WIDTH`.
+    #[default]
+    Tight = 0,
+    /// Every word starts on a byte boundary, `stride = WIDTH` rounded up to
+    /// a multiple of 8.
+    Bytes = 1,
+    /// Every word takes the next power of two bits, at least 8, so the buffer
+    /// can be viewed as an array of 8, 16, 32, 64... bit integers.
+    PowerOfTwo = 2,
+}
+
+impl Packing {
+    /// Number of bits reserved for each word of `width` bits.
+    pub fn stride(&self, width: usize) -> usize {
+        match self {
+            Self::Tight => width,
+            Self::Bytes => width.div_ceil(8) * 8,
+            Self::PowerOfTwo => width.next_power_of_two().max(8),
+        }
+    }
+}
+
+/// Byte order of the buffer. It never depends on the host, so a MIF always
+/// yields the same bytes on every target.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Endianness {
+    /// Stream bit `q` is bit `q % 8` of byte `q / 8`, counting from the least
+    /// significant bit, so byte aligned words come least significant byte
+    /// first.
+    #[default]
+    Little = 0,
+    /// Stream bit `q` is bit `7 - q % 8` of byte `q / 8` and words enter the
+    /// stream most significant bit first, so byte aligned words come most
+    /// significant byte first.
+    Big = 1,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Layout {
+    pub packing: Packing,
+    pub endianness: Endianness,
+}
+
+#[wasm_bindgen]
+impl Layout {
+    #[wasm_bindgen(constructor)]
+    pub fn new(packing: Packing, endianness: Endianness) -> Self {
+        Self {
+            packing,
+            endianness,
+        }
+    }
+}
+
+pub(crate) fn pack<'a>(
+    words: impl ExactSizeIterator<Item = Word<'a>>,
+    width: usize,
+    layout: Layout,
+) -> Vec<u8> {
+    let stride = layout.packing.stride(width);
+    let mut bytes = vec![0u8; (stride * words.len()).div_ceil(8)];
+
+    for (n, word) in words.enumerate() {
+        let start = n * stride;
+
+        match layout.endianness {
+            Endianness::Little => {
+                let mut bit = 0;
+
+                while bit < width {
+                    let offset = start + bit;
+                    let count = (8 - offset % 8).min(width - bit);
+
+                    bytes[offset / 8] |=
+                        (word::extract(word.limbs(), bit, count) as u8) << (offset % 8);
+                    bit += count;
+                }
+            }
+            Endianness::Big => {
+                let mut bit = width;
+
+                while bit > 0 {
+                    let offset = start + stride - bit;
+                    let count = (8 - offset % 8).min(bit);
+
+                    bytes[offset / 8] |= (word::extract(word.limbs(), bit - count, count) as u8)
+                        << (8 - offset % 8 - count);
+                    bit -= count;
+                }
+            }
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::parse;
+
+    use super::*;
+
+    const PACKINGS: [Packing; 3] = [Packing::Tight, Packing::Bytes, Packing::PowerOfTwo];
+    const ENDIANNESSES: [Endianness; 2] = [Endianness::Little, Endianness::Big];
+
+    fn values(width: usize) -> Vec<u128> {
+        let mask = u128::MAX >> (128 - width);
+
+        [
+            0,
+            u128::MAX,
+            0x0123_4567_89AB_CDEF_FEDC_BA98_7654_3210,
+            0x5555_5555_5555_5555_5555_5555_5555_5555,
+            1,
+        ]
+        .iter()
+        .map(|value| value & mask)
+        .collect()
+    }
+
+    #[test]
+    pub fn every_width() {
+        for width in 1..=128 {
+            let values = values(width);
+            let content = values
+                .iter()
+                .map(|value| format!("{value:X}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mif = parse(format!(
+                "WIDTH={width};\nDEPTH={};\nADDRESS_RADIX=UNS;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n0: {content};\nEND;",
+                values.len()
+            ))
+            .unwrap();
+
+            for packing in PACKINGS {
+                for endianness in ENDIANNESSES {
+                    let stride = packing.stride(width);
+                    let bytes = mif.to_bytes(Layout::new(packing, endianness));
+
+                    assert_eq!(bytes.len(), (stride * values.len()).div_ceil(8));
+
+                    for (n, value) in values.iter().enumerate() {
+                        for i in 0..stride {
+                            let expected = i < width && value >> i & 1 == 1;
+                            let actual = match endianness {
+                                Endianness::Little => {
+                                    let offset = n * stride + i;
+                                    bytes[offset / 8] >> (offset % 8) & 1
+                                }
+                                Endianness::Big => {
+                                    let offset = n * stride + stride - 1 - i;
+                                    bytes[offset / 8] >> (7 - offset % 8) & 1
+                                }
+                            };
+
+                            assert_eq!(
+                                actual == 1,
+                                expected,
+                                "width {width}, {packing:?}, {endianness:?}, word {n}, bit {i}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn odd_widths() {
+        let mif = parse(
+            "WIDTH=12;\nDEPTH=2;\nADDRESS_RADIX=UNS;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n0: ABC DEF;\nEND;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            mif.to_bytes(Layout::new(Packing::Tight, Endianness::Little)),
+            [0xBC, 0xFA, 0xDE]
+        );
+        assert_eq!(
+            mif.to_bytes(Layout::new(Packing::Bytes, Endianness::Little)),
+            [0xBC, 0x0A, 0xEF, 0x0D]
+        );
+        assert_eq!(
+            mif.to_bytes(Layout::new(Packing::PowerOfTwo, Endianness::Little)),
+            [0xBC, 0x0A, 0xEF, 0x0D]
+        );
+
+        let mif = parse(
+            "WIDTH=17;\nDEPTH=2;\nADDRESS_RADIX=UNS;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n0: 1FFFF 1;\nEND;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            mif.to_bytes(Layout::new(Packing::Tight, Endianness::Little)),
+            [0xFF, 0xFF, 0x03, 0x00, 0x00]
+        );
+        assert_eq!(
+            mif.to_bytes(Layout::new(Packing::Bytes, Endianness::Little)),
+            [0xFF, 0xFF, 0x01, 0x01, 0x00, 0x00]
+        );
+        assert_eq!(
+            mif.to_bytes(Layout::new(Packing::PowerOfTwo, Endianness::Little)),
+            [0xFF, 0xFF, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    pub fn big_endian() {
+        let mif = parse(
+            "WIDTH=12;\nDEPTH=2;\nADDRESS_RADIX=UNS;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n0: ABC DEF;\nEND;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            mif.to_bytes(Layout::new(Packing::Tight, Endianness::Big)),
+            [0xAB, 0xCD, 0xEF]
+        );
+        assert_eq!(
+            mif.to_bytes(Layout::new(Packing::Bytes, Endianness::Big)),
+            [0x0A, 0xBC, 0x0D, 0xEF]
+        );
+
+        let mif = parse(
+            "WIDTH=17;\nDEPTH=2;\nADDRESS_RADIX=UNS;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n0: 1FFFF 1;\nEND;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            mif.to_bytes(Layout::new(Packing::PowerOfTwo, Endianness::Big)),
+            [0x00, 0x01, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x01]
+        );
+    }
+}
@@ -0,0 +1,418 @@
+use std::ops::Range;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, tag_no_case},
+    character::complete::space1,
+    combinator::{map, recognize},
+    multi::separated_list1,
+};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    error::Utf16,
+    parser::{self, Header, IResult, Terminator, BOM},
+    Radix,
+};
+
+/// What a [`Token`] is, for syntax highlighting.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TokenKind {
+    /// `CONTENT`, `BEGIN` or `END`.
+    Keyword = 0,
+    /// `WIDTH`, `DEPTH`, `ADDRESS_RADIX` or `DATA_RADIX`.
+    HeaderName = 1,
+    Radix = 2,
+    /// The value of `WIDTH` or `DEPTH`, or a data value.
+    Number = 3,
+    /// The address of a data entry, a single one or a `[from..to]` range.
+    Address = 4,
+    Equals = 5,
+    Colon = 6,
+    Semicolon = 7,
+    Comment = 8,
+    /// Text the parser rejects, up to the end of its statement.
+    Error = 9,
+}
+
+/// A token covering the byte range `start..end` of the input, or the range
+/// of UTF-16 code units from the wasm `tokenizeMif`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+impl Token {
+    pub fn span(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+#[wasm_bindgen]
+impl Token {
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn end(&self) -> usize {
+        self.end
+    }
+}
+
+/// Splits `source` into tokens, in order, using the parser's grammar.
+/// Whitespace isn't part of any token.
+///
+/// Like [`parser::parse_recovering`], a statement that doesn't parse gives
+/// an [`TokenKind::Error`] token from where it goes wrong up to its `;`, and
+/// tokenizing goes on after it. Radixes the header doesn't declare are taken
+/// to be `HEX`.
+pub fn tokenize(source: impl AsRef<[u8]>) -> Vec<Token> {
+    let source = parser::decode(source.as_ref());
+    let mut lexer = Lexer {
+        source: &source,
+        input: &source,
+        tokens: Vec::new(),
+        address_radix: Radix::Hex,
+        data_radix: Radix::Hex,
+    };
+
+    lexer.run();
+    lexer.tokens
+}
+
+/// Like [`tokenize`], with spans counting UTF-16 code units, the way JS
+/// indexes `input`.
+#[wasm_bindgen(js_name = "tokenizeMif")]
+pub fn tokenize_mif(input: &str) -> Vec<Token> {
+    let offsets = Utf16::new(input);
+
+    tokenize(input)
+        .into_iter()
+        .map(|token| {
+            let span = offsets.span(token.span());
+
+            Token {
+                start: span.start,
+                end: span.end,
+                ..token
+            }
+        })
+        .collect()
+}
+
+/// Like [`tokenize`], for a file given as bytes, which may not be valid
+/// UTF-8. Spans are byte offsets into `input`.
+#[wasm_bindgen(js_name = "tokenizeMifBytes")]
+pub fn tokenize_mif_bytes(input: &[u8]) -> Vec<Token> {
+    tokenize(input)
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    input: &'a str,
+    tokens: Vec<Token>,
+    address_radix: Radix,
+    data_radix: Radix,
+}
+
+impl<'a> Lexer<'a> {
+    fn run(&mut self) {
+        loop {
+            self.trivia();
+
+            if self.input.is_empty() {
+                return;
+            }
+
+            if self
+                .next(TokenKind::Keyword, tag_no_case("CONTENT"))
+                .is_some()
+            {
+                break;
+            }
+
+            if self.attribute().is_none() {
+                self.error();
+            }
+        }
+
+        self.trivia();
+
+        if self
+            .next(TokenKind::Keyword, tag_no_case("BEGIN"))
+            .is_none()
+        {
+            self.error();
+        }
+
+        loop {
+            self.trivia();
+
+            if self.input.is_empty() {
+                return;
+            }
+
+            if self.next(TokenKind::Keyword, tag_no_case("END")).is_some() {
+                self.space();
+
+                if self.next(TokenKind::Semicolon, tag(";")).is_none() {
+                    self.error();
+                }
+
+                break;
+            }
+
+            if self.entry().is_none() {
+                self.error();
+            }
+        }
+
+        // Anything but comments after `END;` is a single error, as in the
+        // parser.
+        self.trivia();
+        self.push(TokenKind::Error, self.input.trim_end());
+    }
+
+    /// Takes whitespace and comments.
+    fn trivia(&mut self) {
+        loop {
+            self.input = self.input.trim_start_matches(BOM).trim_start();
+
+            if self.next(TokenKind::Comment, parser::comment).is_none() {
+                break;
+            }
+        }
+    }
+
+    fn space(&mut self) {
+        self.input = self.input.trim_start();
+    }
+
+    fn attribute(&mut self) -> Option<()> {
+        let header = self.next(TokenKind::HeaderName, header_name)?;
+
+        self.space();
+        self.next(TokenKind::Equals, tag("="))?;
+        self.space();
+
+        match header {
+            Header::Width | Header::Depth => {
+                self.next(TokenKind::Number, |input| parser::digits(input, Radix::Uns))?;
+            }
+            Header::AddressRadix => {
+                self.address_radix = self.next(TokenKind::Radix, parser::radix)?;
+            }
+            Header::DataRadix => {
+                self.data_radix = self.next(TokenKind::Radix, parser::radix)?;
+            }
+        }
+
+        self.space();
+        self.next(TokenKind::Semicolon, tag(";"))?;
+
+        Some(())
+    }
+
+    fn entry(&mut self) -> Option<()> {
+        let (address_radix, data_radix) = (self.address_radix, self.data_radix);
+
+        self.next(TokenKind::Address, |input| {
+            parser::address(input, address_radix)
+        })?;
+        self.space();
+        self.next(TokenKind::Colon, tag(":"))?;
+        self.space();
+
+        let (rest, values) = separated_list1(
+            space1,
+            recognize(|input| parser::value(input, data_radix, 1)),
+        )(self.input)
+        .ok()?;
+
+        for value in values {
+            self.push(TokenKind::Number, value);
+        }
+
+        self.input = rest;
+        self.space();
+        self.next(TokenKind::Semicolon, tag(";"))?;
+
+        Some(())
+    }
+
+    /// Runs `parser` on the input, taking what it matches as a `kind` token.
+    fn next<T>(
+        &mut self,
+        kind: TokenKind,
+        mut parser: impl FnMut(&'a str) -> IResult<'a, T>,
+    ) -> Option<T> {
+        let (rest, value) = parser(self.input).ok()?;
+
+        self.push(kind, &self.input[..self.input.len() - rest.len()]);
+        self.input = rest;
+
+        Some(value)
+    }
+
+    /// Takes the rest of the statement as an error.
+    fn error(&mut self) {
+        let length = Terminator::default()
+            .find(self.input.as_bytes())
+            .map_or(self.input.len(), |i| i + 1);
+        let (text, rest) = self.input.split_at(length);
+
+        self.push(TokenKind::Error, text.trim());
+        self.input = rest;
+    }
+
+    /// Adds a `kind` token for `text`, a slice of the source.
+    fn push(&mut self, kind: TokenKind, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        let start = text.as_ptr() as usize - self.source.as_ptr() as usize;
+
+        self.tokens.push(Token {
+            kind,
+            start,
+            end: start + text.len(),
+        });
+    }
+}
+
+fn header_name(input: &str) -> IResult<'_, Header> {
+    alt((
+        map(tag_no_case("WIDTH"), |_| Header::Width),
+        map(tag_no_case("DEPTH"), |_| Header::Depth),
+        map(tag_no_case("ADDRESS_RADIX"), |_| Header::AddressRadix),
+        map(tag_no_case("DATA_RADIX"), |_| Header::DataRadix),
+    ))(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn kinds(input: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(input)
+            .into_iter()
+            .map(|token| (token.kind(), &input[token.span()]))
+            .collect()
+    }
+
+    #[test]
+    pub fn tokens() {
+        use TokenKind::*;
+
+        assert_eq!(
+            kinds("-- rom\nwidth = 8;\nDEPTH=16;\nADDRESS_RADIX=UNS;\nDATA_RADIX=dec;\nCONTENT % c % BEGIN\n[0..3] : -1 2;\n12: 7;-- x\nEND;\n"),
+            [
+                (Comment, "-- rom"),
+                (HeaderName, "width"),
+                (Equals, "="),
+                (Number, "8"),
+                (Semicolon, ";"),
+                (HeaderName, "DEPTH"),
+                (Equals, "="),
+                (Number, "16"),
+                (Semicolon, ";"),
+                (HeaderName, "ADDRESS_RADIX"),
+                (Equals, "="),
+                (Radix, "UNS"),
+                (Semicolon, ";"),
+                (HeaderName, "DATA_RADIX"),
+                (Equals, "="),
+                (Radix, "dec"),
+                (Semicolon, ";"),
+                (Keyword, "CONTENT"),
+                (Comment, "% c %"),
+                (Keyword, "BEGIN"),
+                (Address, "[0..3]"),
+                (Colon, ":"),
+                (Number, "-1"),
+                (Number, "2"),
+                (Semicolon, ";"),
+                (Address, "12"),
+                (Colon, ":"),
+                (Number, "7"),
+                (Semicolon, ";"),
+                (Comment, "-- x"),
+                (Keyword, "END"),
+                (Semicolon, ";"),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn errors() {
+        use TokenKind::*;
+
+        assert_eq!(
+            kinds("WIDTH=8;\nDEPTH=x;\nCONTENT BEGIN\n0: 1G;\n1 = 2; -- ok\n2: 3;\nEND;\nfoo;\n-- bar\n"),
+            [
+                (HeaderName, "WIDTH"),
+                (Equals, "="),
+                (Number, "8"),
+                (Semicolon, ";"),
+                (HeaderName, "DEPTH"),
+                (Equals, "="),
+                (Error, "x;"),
+                (Keyword, "CONTENT"),
+                (Keyword, "BEGIN"),
+                (Address, "0"),
+                (Colon, ":"),
+                (Error, "1G;"),
+                (Address, "1"),
+                (Error, "= 2;"),
+                (Comment, "-- ok"),
+                (Address, "2"),
+                (Colon, ":"),
+                (Number, "3"),
+                (Semicolon, ";"),
+                (Keyword, "END"),
+                (Semicolon, ";"),
+                (Error, "foo;\n-- bar"),
+            ]
+        );
+
+        let tokens = tokenize_mif_bytes(b"-- caf\xE9\nWIDTH=8;");
+
+        assert_eq!(tokens[0].span(), 0..7);
+        assert_eq!(tokens[1].kind(), HeaderName);
+    }
+
+    #[test]
+    pub fn utf16() {
+        let input = "-- caf\u{E9} \u{1F600}\nWIDTH=8; % \u{2603} % DEPTH=4;";
+        let units = input.encode_utf16().collect::<Vec<_>>();
+        let text = |token: &Token| String::from_utf16(&units[token.start()..token.end()]).unwrap();
+
+        assert_eq!(
+            tokenize_mif(input).iter().map(text).collect::<Vec<_>>(),
+            [
+                "-- caf\u{E9} \u{1F600}",
+                "WIDTH",
+                "=",
+                "8",
+                ";",
+                "% \u{2603} %",
+                "DEPTH",
+                "=",
+                "4",
+                ";"
+            ]
+        );
+    }
+}
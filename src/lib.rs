@@ -1,10 +1,25 @@
-#![feature(integer_sign_cast, f16, f128)]
 #![cfg_attr(feature = "f16", feature(f16))]
 #![cfg_attr(feature = "f128", feature(f128))]
 
+pub mod document;
+mod encoder;
+pub mod error;
+pub mod layout;
+pub mod lexer;
 pub mod parser;
 pub mod radix;
+pub mod stream;
+pub mod syntax;
+pub mod word;
 pub mod writer;
 
+pub use document::{Annotation, MifDocument, Placement, SourceSpan};
+pub use error::{Diagnostic, MifError, Severity};
+pub use layout::{Endianness, Layout, Packing};
+pub use lexer::{tokenize, Token, TokenKind};
+pub use parser::{Overlap, ParseOptions, Truncation};
 pub use radix::Radix;
-pub use writer::Mif;
+pub use stream::StreamParser;
+pub use syntax::SyntaxTree;
+pub use word::Word;
+pub use writer::{Encoding, FormatOptions, Mif};
@@ -1,23 +1,181 @@
+use std::{borrow::Cow, fmt::Display, ops::Range};
+
 use nom::{
     branch::alt,
-    bytes::complete::{is_a, is_not, tag, take_while},
-    character::complete::{char, space1},
-    combinator::map,
+    bytes::complete::{is_not, tag, tag_no_case, take_while, take_while1},
+    character::complete::{char, multispace0, space1},
+    combinator::{cut, map, opt, recognize},
+    error::ParseError,
     multi::{many0, many0_count, separated_list1},
-    sequence::delimited,
-    IResult,
+    sequence::{delimited, preceded},
 };
 use wasm_bindgen::prelude::*;
 
-use crate::Radix;
+use crate::{
+    document::{Annotation, MifDocument, Placement, SourceSpan},
+    error::{location, Diagnostic, ErrorKind, Lines, MifError, Severity, Utf16},
+    layout::Layout,
+    word::{self, Word},
+    Radix,
+};
+
+pub(crate) type IResult<'a, T> = nom::IResult<&'a str, T, Error<'a>>;
 
-#[derive(Debug)]
-struct Mif {
-    address_radix: Radix,
-    data_radix: Radix,
-    depth: usize,
-    width: usize,
-    chunks: Vec<usize>,
+#[derive(Debug, PartialEq)]
+pub(crate) struct Error<'a> {
+    input: &'a str,
+    kind: ErrorKind,
+}
+
+impl<'a> ParseError<&'a str> for Error<'a> {
+    fn from_error_kind(input: &'a str, _kind: nom::error::ErrorKind) -> Self {
+        Self {
+            input,
+            kind: ErrorKind::UnexpectedToken,
+        }
+    }
+
+    fn append(_input: &'a str, _kind: nom::error::ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn or(self, other: Self) -> Self {
+        // Keep whichever alternative made it further into the input.
+        if other.input.len() < self.input.len() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+fn failure<T>(input: &str, kind: ErrorKind) -> IResult<'_, T> {
+    Err(nom::Err::Failure(Error { input, kind }))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Header {
+    Width,
+    Depth,
+    AddressRadix,
+    DataRadix,
+}
+
+impl Display for Header {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Width => "WIDTH",
+            Self::Depth => "DEPTH",
+            Self::AddressRadix => "ADDRESS_RADIX",
+            Self::DataRadix => "DATA_RADIX",
+        })
+    }
+}
+
+/// What happens when `CONTENT` assigns an address more than once.
+///
+/// Every address assigned again is reported: as a warning when one of the
+/// values is kept, as an error with [`Overlap::Error`].
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Overlap {
+    /// The entry written last sets the word.
+    #[default]
+    LastWins = 0,
+    /// The entry written first sets the word, later ones are ignored.
+    FirstWins = 1,
+    /// Assigning an address again is an error.
+    Error = 2,
+}
+
+/// What happens to a value wider than `WIDTH`. Values that are kept are
+/// truncated to their low `WIDTH` bits.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Truncation {
+    /// A value that doesn't fit is an error.
+    Error = 0,
+    /// A value that doesn't fit is truncated and reported as a warning.
+    #[default]
+    Warning = 1,
+    /// A value that doesn't fit is silently truncated.
+    Allow = 2,
+}
+
+/// How [`parse_with`] and [`parse_recovering_with`] handle the problems a
+/// file can have.
+///
+/// Header attributes missing from the file take their default from here,
+/// with a warning, unless `strict` is set. By default both radixes are `HEX`
+/// and `WIDTH` and `DEPTH` have no default.
+#[wasm_bindgen]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseOptions {
+    pub address_radix: Option<Radix>,
+    pub data_radix: Option<Radix>,
+    pub width: Option<usize>,
+    pub depth: Option<usize>,
+    /// Every header attribute must be in the file.
+    pub strict: bool,
+    pub overlap: Overlap,
+    pub truncation: Truncation,
+    /// Words written to every address `CONTENT` doesn't assign, each as
+    /// little-endian 64-bit limbs: address `n` gets word `n % count` of the
+    /// `count` words, zero extended or truncated to `WIDTH`. Empty fills
+    /// with zeros.
+    #[wasm_bindgen(skip)]
+    pub fill: Vec<Vec<u64>>,
+    /// Record which entry set each address, see [`MifDocument::source_map`].
+    pub source_map: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            address_radix: Some(Radix::Hex),
+            data_radix: Some(Radix::Hex),
+            width: None,
+            depth: None,
+            strict: false,
+            overlap: Overlap::default(),
+            truncation: Truncation::default(),
+            fill: Vec::new(),
+            source_map: false,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl ParseOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The low 64 bits of each fill word.
+    #[wasm_bindgen(getter, js_name = "fill")]
+    pub fn get_fill(&self) -> Vec<u64> {
+        self.fill
+            .iter()
+            .map(|word| word.first().copied().unwrap_or(0))
+            .collect()
+    }
+
+    /// Fills with one word of up to 64 bits per entry.
+    #[wasm_bindgen(setter)]
+    pub fn set_fill(&mut self, fill: Vec<u64>) {
+        self.fill = fill.into_iter().map(|word| vec![word]).collect();
+    }
+
+    /// Fills with words wider than 64 bits, each `per_word` little-endian
+    /// limbs of `limbs`.
+    #[wasm_bindgen(js_name = "setFillLimbs")]
+    pub fn set_fill_limbs(&mut self, limbs: Vec<u64>, per_word: usize) {
+        self.fill = limbs
+            .chunks(per_word.max(1))
+            .map(|word| word.to_vec())
+            .collect();
+    }
 }
 
 #[derive(Default, Debug)]
@@ -29,9 +187,54 @@ struct MifBuilder {
 }
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
-struct Address {
-    from: usize,
-    to: usize,
+pub struct Address {
+    pub from: usize,
+    pub to: usize,
+}
+
+/// The attributes declared before `CONTENT`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MifHeader {
+    pub width: usize,
+    pub depth: usize,
+    pub address_radix: Radix,
+    pub data_radix: Radix,
+}
+
+/// A data entry of `CONTENT`, with its address and values as written.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Record {
+    address: Address,
+    width: usize,
+    limbs: Vec<u64>,
+}
+
+impl Record {
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    pub fn values(&self) -> impl ExactSizeIterator<Item = Word<'_>> + Clone + '_ {
+        self.limbs
+            .chunks_exact(word::limbs(self.width))
+            .map(|limbs| Word::new(limbs, self.width))
+    }
+
+    /// Every address this entry assigns. A single address takes the values
+    /// one after the other, a range repeats them cyclically.
+    pub fn addresses(&self) -> Range<usize> {
+        let Address { from, to } = self.address;
+
+        match from == to {
+            true => from..from + self.values().len(),
+            false => from..to + 1,
+        }
+    }
+
+    /// Every address this entry assigns, with its word.
+    pub fn assignments(&self) -> impl Iterator<Item = (usize, Word<'_>)> + '_ {
+        self.addresses().zip(self.values().cycle())
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -44,7 +247,7 @@ pub enum Element {
     Data(Address, Vec<usize>),
 }
 
-fn multiline_comment(input: &str) -> IResult<&str, Element> {
+fn multiline_comment(input: &str) -> IResult<'_, Element> {
     let (input, _) = take_while(char::is_whitespace)(input)?;
     let (input, _) = delimited(char('%'), is_not("%"), char('%'))(input)?;
     let (input, _) = take_while(char::is_whitespace)(input)?;
@@ -52,18 +255,49 @@ fn multiline_comment(input: &str) -> IResult<&str, Element> {
     Ok((input, Element::Comment))
 }
 
-fn singleline_comment(input: &str) -> IResult<&str, Element> {
+/// A `--` comment up to the end of the line or a `%` comment, with its
+/// delimiters.
+pub(crate) fn comment(input: &str) -> IResult<'_, &str> {
+    recognize(alt((
+        preceded(tag("--"), take_while(|c| c != '\n' && c != '\r')),
+        delimited(char('%'), is_not("%"), char('%')),
+    )))(input)
+}
+
+/// The text of a comment after any whitespace, without its delimiters.
+fn comment_text(input: &str) -> IResult<'_, &str> {
+    let (input, _) = take_while(char::is_whitespace)(input)?;
+    let (input, comment) = comment(input)?;
+    let text = match comment.strip_prefix("--") {
+        Some(text) => text,
+        None => &comment[1..comment.len() - 1],
+    };
+
+    Ok((input, text.trim()))
+}
+
+/// A `--` comment, running to the end of the line or of the input.
+fn singleline_comment(input: &str) -> IResult<'_, Element> {
     let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, _) = delimited(tag("--"), is_not("\n"), char('\n'))(input)?;
+    let (input, _) = tag("--")(input)?;
+    let (input, _) = take_while(|c| c != '\n' && c != '\r')(input)?;
+    let (input, _) = opt(alt((tag("\r\n"), tag("\n"), tag("\r"))))(input)?;
 
     Ok((input, Element::Comment))
 }
 
-fn radix(input: &str) -> IResult<&str, Radix> {
+pub(crate) fn radix(input: &str) -> IResult<'_, Radix> {
     map(
-        alt((tag("UNS"), tag("BIN"), tag("HEX"), tag("OCT"))),
-        |s: &str| match s {
+        alt((
+            tag_no_case("UNS"),
+            tag_no_case("DEC"),
+            tag_no_case("BIN"),
+            tag_no_case("HEX"),
+            tag_no_case("OCT"),
+        )),
+        |s: &str| match s.to_ascii_uppercase().as_str() {
             "UNS" => Radix::Uns,
+            "DEC" => Radix::Dec,
             "BIN" => Radix::Bin,
             "OCT" => Radix::Oct,
             "HEX" => Radix::Hex,
@@ -72,64 +306,109 @@ fn radix(input: &str) -> IResult<&str, Radix> {
     )(input)
 }
 
-fn number<'a>(input: &'a str, radix: Radix) -> IResult<&'a str, usize> {
-    let (input, number) = is_a(radix.digits())(input)?;
-    let number = usize::from_str_radix(number, radix.radix()).unwrap();
+/// Takes a run of digits, failing on the first one that isn't valid for
+/// `radix`.
+pub(crate) fn digits(input: &str, radix: Radix) -> IResult<'_, &str> {
+    let (rest, digits) = take_while1(|c: char| c.is_ascii_alphanumeric())(input)?;
 
-    Ok((input, number))
+    if let Some(i) = digits.find(|c: char| !radix.digits().contains(c)) {
+        return failure(&input[i..], ErrorKind::InvalidDigit(radix));
+    }
+
+    Ok((rest, digits))
+}
+
+fn number(input: &str, radix: Radix) -> IResult<'_, usize> {
+    let (rest, digits) = digits(input, radix)?;
+
+    match usize::from_str_radix(digits, radix.radix()) {
+        Ok(number) => Ok((rest, number)),
+        Err(_) => failure(input, ErrorKind::NumberOverflow),
+    }
 }
 
-fn numeric_attribute<'a>(input: &'a str, name: &str) -> IResult<&'a str, usize> {
+/// Parses a data value into `width`-bit limbs, along with whether it had to
+/// be truncated to fit. `DEC` values may be negative, in which case they are
+/// stored as two's complement and must fit the signed range.
+pub(crate) fn value(input: &str, radix: Radix, width: usize) -> IResult<'_, (Vec<u64>, bool)> {
+    let (rest, sign) = match radix {
+        Radix::Dec => opt(char('-'))(input)?,
+        _ => (input, None),
+    };
+    let (rest, digits) = digits(rest, radix)?;
+    let limbs = word::limbs(width).max(word::limbs(digits.len() * 4));
+    let mut value =
+        word::from_digits(digits, radix.radix(), limbs).expect("four bits hold any digit");
+    let bits = word::significant_bits(&value);
+
+    let fits = match sign {
+        Some(_) => {
+            let is_min =
+                bits == width && value.iter().map(|limb| limb.count_ones()).sum::<u32>() == 1;
+
+            word::negate(&mut value, limbs * 64);
+            bits < width || is_min
+        }
+        None => bits <= width,
+    };
+
+    word::mask(&mut value, width);
+    value.truncate(word::limbs(width));
+
+    Ok((rest, (value, !fits)))
+}
+
+fn numeric_attribute<'a>(input: &'a str, name: &str) -> IResult<'a, usize> {
     let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, _) = tag(name)(input)?;
+    let (input, _) = tag_no_case(name)(input)?;
     let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, _) = tag("=")(input)?;
+    let (input, _) = cut(tag("="))(input)?;
     let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, number) = take_while(|c: char| c.is_ascii_digit())(input)?;
+    let (input, number) = cut(|input| number(input, Radix::Uns))(input)?;
     let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, _) = tag(";")(input)?;
+    let (input, _) = cut(tag(";"))(input)?;
 
-    Ok((input, number.parse::<usize>().unwrap()))
+    Ok((input, number))
 }
 
-fn radix_attribute<'a>(input: &'a str, name: &str) -> IResult<&'a str, Radix> {
+fn radix_attribute<'a>(input: &'a str, name: &str) -> IResult<'a, Radix> {
     let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, _) = tag(name)(input)?;
+    let (input, _) = tag_no_case(name)(input)?;
     let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, _) = tag("=")(input)?;
+    let (input, _) = cut(tag("="))(input)?;
     let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, radix) = radix(input)?;
+    let (input, radix) = cut(radix)(input)?;
     let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, _) = tag(";")(input)?;
+    let (input, _) = cut(tag(";"))(input)?;
 
     Ok((input, radix))
 }
 
-fn width(input: &str) -> IResult<&str, Element> {
+fn width(input: &str) -> IResult<'_, Element> {
     let (input, value) = numeric_attribute(input, "WIDTH")?;
     Ok((input, Element::Width(value)))
 }
 
-fn depth(input: &str) -> IResult<&str, Element> {
+fn depth(input: &str) -> IResult<'_, Element> {
     let (input, value) = numeric_attribute(input, "DEPTH")?;
     Ok((input, Element::Depth(value)))
 }
 
-fn address_radix(input: &str) -> IResult<&str, Element> {
+fn address_radix(input: &str) -> IResult<'_, Element> {
     let (input, value) = radix_attribute(input, "ADDRESS_RADIX")?;
     Ok((input, Element::AddressRadix(value)))
 }
 
-fn data_radix(input: &str) -> IResult<&str, Element> {
+fn data_radix(input: &str) -> IResult<'_, Element> {
     let (input, value) = radix_attribute(input, "DATA_RADIX")?;
     Ok((input, Element::DataRadix(value)))
 }
 
-fn address_range<'a>(input: &'a str, radix: Radix) -> IResult<&'a str, Address> {
+fn address_range(input: &str, radix: Radix) -> IResult<'_, Address> {
     let (input, _) = take_while(char::is_whitespace)(input)?;
     let (input, address) = delimited(
         char('['),
-        |input: &'a str| {
+        cut(|input| {
             let (input, _) = take_while(char::is_whitespace)(input)?;
             let (input, from) = number(input, radix)?;
             let (input, _) = take_while(char::is_whitespace)(input)?;
@@ -139,139 +418,792 @@ fn address_range<'a>(input: &'a str, radix: Radix) -> IResult<&'a str, Address>
             let (input, _) = take_while(char::is_whitespace)(input)?;
 
             Ok((input, Address { from, to }))
-        },
-        char(']'),
+        }),
+        cut(char(']')),
     )(input)?;
 
     Ok((input, address))
 }
 
-fn address_number<'a>(input: &'a str, radix: Radix) -> IResult<&'a str, Address> {
+fn address_number(input: &str, radix: Radix) -> IResult<'_, Address> {
     let (input, _) = take_while(char::is_whitespace)(input)?;
     let (input, num) = number(input, radix)?;
 
     Ok((input, Address { from: num, to: num }))
 }
 
-fn data<'a>(input: &'a str, mif: &mut Mif) -> IResult<&'a str, Element> {
+/// Parses the address of a data entry, either a single one or a range.
+pub(crate) fn address(input: &str, radix: Radix) -> IResult<'_, Address> {
+    alt((
+        |input| address_range(input, radix),
+        |input| address_number(input, radix),
+    ))(input)
+}
+
+/// Parses a data entry, along with every value in it that had to be
+/// truncated, unless `truncation` makes those an error.
+fn data<'a>(
+    input: &'a str,
+    header: &MifHeader,
+    truncation: Truncation,
+) -> IResult<'a, (Record, Vec<&'a str>)> {
     let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, address) = alt((
-        |input| address_range(input, mif.address_radix),
-        |input| address_number(input, mif.address_radix),
-    ))(input)?;
+    let start = input;
+    let (input, address) = address(input, header.address_radix)?;
+
+    if address.from > address.to {
+        return failure(start, ErrorKind::ReversedRange);
+    }
+
+    if address.from >= header.depth || address.to >= header.depth {
+        return failure(start, ErrorKind::AddressOutOfRange);
+    }
+
     let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, _) = tag(":")(input)?;
+    let (input, _) = cut(tag(":"))(input)?;
+    let (input, _) = take_while(char::is_whitespace)(input)?;
+    let (input, values) = cut(separated_list1(space1, |input: &'a str| {
+        let (rest, (value, truncated)) = value(input, header.data_radix, header.width)?;
+
+        Ok((rest, (value, truncated.then_some((input, rest)))))
+    }))(input)?;
+    let truncated = values
+        .iter()
+        .filter_map(|(_, truncated)| *truncated)
+        .collect::<Vec<_>>();
+
+    if let (Truncation::Error, Some((value, _))) = (truncation, truncated.first()) {
+        return failure(value, ErrorKind::ValueOutOfRange);
+    }
     let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, values) = separated_list1(space1, |input| number(input, mif.data_radix))(input)?;
-    let (input, _) = tag(";")(input)?;
+    let (input, _) = cut(tag(";"))(input)?;
     let (input, _) = take_while(char::is_whitespace)(input)?;
 
-    let mask = (1usize << mif.width as u32).wrapping_sub(1);
-    if address.from == address.to {
-        for (i, mut value) in (address.from..).zip(values.into_iter()) {
-            let offset = i * mif.width;
-            let index = offset / usize::BITS as usize;
-            let shift = offset % usize::BITS as usize;
+    if address.from == address.to && address.from + values.len() > header.depth {
+        return failure(start, ErrorKind::AddressOutOfRange);
+    }
+
+    let record = Record {
+        address,
+        width: header.width,
+        limbs: values.into_iter().flat_map(|(value, _)| value).collect(),
+    };
+
+    let truncated = match truncation {
+        Truncation::Allow => Vec::new(),
+        _ => truncated
+            .into_iter()
+            .map(|(value, rest)| &value[..value.len() - rest.len()])
+            .collect(),
+    };
+
+    Ok((input, (record, truncated)))
+}
+
+fn comments(input: &str) -> IResult<'_, usize> {
+    many0_count(alt((singleline_comment, multiline_comment)))(input)
+}
+
+fn trivia(input: &str) -> IResult<'_, ()> {
+    let (input, _) = opt(char(BOM))(input)?;
+    let (input, _) = comments(input)?;
+    let (input, _) = multispace0(input)?;
+
+    Ok((input, ()))
+}
+
+pub(crate) fn skip_trivia(input: &str) -> &str {
+    trivia(input).map_or(input, |(input, _)| input)
+}
 
-            value &= mask;
+pub(crate) fn attribute(input: &str) -> IResult<'_, Element> {
+    alt((width, depth, address_radix, data_radix))(input)
+}
 
-            let (lower_chunk, overflow) = value.overflowing_shl(shift as u32);
-            mif.chunks[index] |= lower_chunk;
+pub(crate) fn begin(input: &str) -> IResult<'_, ()> {
+    let (input, _) = tag_no_case("CONTENT")(input)?;
+    let (input, _) = comments(input)?;
+    let (input, _) = take_while(char::is_whitespace)(input)?;
+    let (input, _) = cut(tag_no_case("BEGIN"))(input)?;
 
-            if overflow {
-                mif.chunks[index + 1] |= value >> (usize::BITS as usize - mask);
+    Ok((input, ()))
+}
+
+impl MifBuilder {
+    fn set(&mut self, element: Element) {
+        match element {
+            Element::DataRadix(radix) => self.data_radix = Some(radix),
+            Element::AddressRadix(radix) => self.address_radix = Some(radix),
+            Element::Width(width) => self.width = Some(width),
+            Element::Depth(depth) => self.depth = Some(depth),
+            _ => (),
+        }
+    }
+
+    /// Sets every attribute that hasn't been set to its default in
+    /// `options`, unless they are strict.
+    fn defaults(&mut self, options: &ParseOptions) {
+        if options.strict {
+            return;
+        }
+
+        self.address_radix = self.address_radix.or(options.address_radix);
+        self.data_radix = self.data_radix.or(options.data_radix);
+        self.width = self.width.or(options.width);
+        self.depth = self.depth.or(options.depth);
+    }
+
+    /// Every required attribute that hasn't been set.
+    fn missing(&self) -> Vec<Header> {
+        [
+            (self.address_radix.is_none(), Header::AddressRadix),
+            (self.data_radix.is_none(), Header::DataRadix),
+            (self.width.is_none(), Header::Width),
+            (self.depth.is_none(), Header::Depth),
+        ]
+        .into_iter()
+        .filter_map(|(missing, header)| missing.then_some(header))
+        .collect()
+    }
+
+    fn build(&self) -> Option<MifHeader> {
+        Some(MifHeader {
+            width: self.width?,
+            depth: self.depth?,
+            address_radix: self.address_radix?,
+            data_radix: self.data_radix?,
+        })
+    }
+}
+
+/// Parses every attribute before `CONTENT`, then `CONTENT` itself up to and
+/// including `BEGIN`.
+fn header<'a>(input: &'a str, options: &ParseOptions) -> IResult<'a, MifHeader> {
+    let (input, _) = opt(char(BOM))(input)?;
+    let (input, elements) = many0(alt((attribute, multiline_comment, singleline_comment)))(input)?;
+    let (input, _) = take_while(char::is_whitespace)(input)?;
+
+    let mut builder = MifBuilder::default();
+    elements
+        .into_iter()
+        .for_each(|element| builder.set(element));
+    builder.defaults(options);
+
+    let Some(header) = builder.build() else {
+        return failure(input, ErrorKind::MissingHeader(builder.missing()[0]));
+    };
+
+    if input.is_empty() {
+        return failure(input, ErrorKind::MissingContent);
+    }
+
+    let (input, _) = begin(input)?;
+
+    Ok((input, header))
+}
+
+pub(crate) fn end(input: &str) -> IResult<'_, ()> {
+    let (input, _) = multispace0(input)?;
+    let (input, _) = tag_no_case("END")(input)?;
+    let (input, _) = take_while(char::is_whitespace)(input)?;
+    let (input, _) = cut(tag(";"))(input)?;
+
+    Ok((input, ()))
+}
+
+/// Parses a single statement of `CONTENT`: either a data entry or `END;`,
+/// which gives `None`. Values wider than `WIDTH` are an error only with
+/// [`Truncation::Error`].
+pub(crate) fn statement<'a>(
+    input: &'a str,
+    header: &MifHeader,
+    truncation: Truncation,
+) -> IResult<'a, Option<Record>> {
+    let (input, _) = trivia(input)?;
+
+    alt((
+        map(end, |_| None),
+        map(
+            |input| data(input, header, truncation),
+            |(record, _)| Some(record),
+        ),
+    ))(input)
+}
+
+/// Accepts only whitespace and comments, as allowed after `END;`.
+pub(crate) fn trailing(input: &str) -> IResult<'_, ()> {
+    let (input, _) = trivia(input)?;
+
+    if !input.is_empty() {
+        return failure(input, ErrorKind::TrailingInput);
+    }
+
+    Ok((input, ()))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+enum Comment {
+    #[default]
+    None,
+    Line,
+    Block,
+}
+
+/// Finds the `;` that ends a statement, skipping over comments. The state is
+/// kept between calls so the input can be fed in pieces.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Terminator {
+    comment: Comment,
+    previous: Option<u8>,
+}
+
+impl Terminator {
+    /// Index of the terminating `;` in `bytes`, if this piece contains it.
+    pub(crate) fn find(&mut self, bytes: &[u8]) -> Option<usize> {
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.comment = match (self.comment, byte) {
+                (Comment::None, b';') => {
+                    *self = Self::default();
+                    return Some(i);
+                }
+                (Comment::None, b'%') => Comment::Block,
+                (Comment::None, b'-') if self.previous == Some(b'-') => Comment::Line,
+                (Comment::Line, b'\n' | b'\r') | (Comment::Block, b'%') => Comment::None,
+                (comment, _) => comment,
+            };
+            self.previous = Some(byte);
+        }
+
+        None
+    }
+}
+
+/// Collects diagnostics while parsing `source`.
+struct Diagnostics<'a> {
+    source: &'a str,
+    diagnostics: Vec<Diagnostic>,
+    /// Built with the first diagnostic, so clean sources never pay for it.
+    lines: Option<Lines<'a>>,
+}
+
+impl<'a> Diagnostics<'a> {
+    fn offset(&self, input: &str) -> usize {
+        self.source.len() - input.len()
+    }
+
+    /// Span of `text`, a slice of the source.
+    fn span(&self, text: &str) -> Range<usize> {
+        let start = text.as_ptr() as usize - self.source.as_ptr() as usize;
+
+        start..start + text.len()
+    }
+
+    fn lines(&mut self) -> &Lines<'a> {
+        self.lines.get_or_insert_with(|| Lines::new(self.source))
+    }
+
+    fn push(&mut self, severity: Severity, kind: ErrorKind, span: Range<usize>) {
+        let diagnostic = Diagnostic::new(severity, kind, self.lines(), span);
+
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Reports `error` and skips past the end of the statement it is in,
+    /// returning the input that follows.
+    fn recover(&mut self, error: nom::Err<Error<'a>>) -> &'a str {
+        let error = match error {
+            nom::Err::Error(error) | nom::Err::Failure(error) => error,
+            nom::Err::Incomplete(_) => unreachable!(),
+        };
+        let start = self.offset(error.input);
+        let length = Terminator::default()
+            .find(error.input.as_bytes())
+            .map_or(error.input.len(), |i| i + 1);
+
+        self.push(
+            Severity::Error,
+            error.kind,
+            start..start + error.input[..length].trim_end().len(),
+        );
+
+        &error.input[length..]
+    }
+}
+
+/// The words of `CONTENT` being filled in, with the statement that set each
+/// address.
+struct Image {
+    mif: MifDocument,
+    owners: Vec<Option<usize>>,
+    spans: Vec<Range<usize>>,
+}
+
+impl Image {
+    /// Writes `record`, found at `span`, following `overlap`. Returns the
+    /// runs of addresses that were already assigned, each with the span of
+    /// the statement that assigned them.
+    fn assign(
+        &mut self,
+        record: &Record,
+        span: Range<usize>,
+        overlap: Overlap,
+    ) -> Vec<(Address, Range<usize>)> {
+        let statement = self.spans.len();
+        let mut runs: Vec<(Address, usize)> = Vec::new();
+
+        self.spans.push(span);
+
+        for (address, value) in record.assignments() {
+            let Some(owner) = self.owners[address] else {
+                self.owners[address] = Some(statement);
+                self.mif.set(address, value);
+                continue;
+            };
+
+            match runs.last_mut() {
+                Some((run, previous)) if *previous == owner && run.to + 1 == address => {
+                    run.to = address;
+                }
+                _ => runs.push((
+                    Address {
+                        from: address,
+                        to: address,
+                    },
+                    owner,
+                )),
+            }
+
+            if overlap == Overlap::LastWins {
+                self.owners[address] = Some(statement);
+                self.mif.set(address, value);
             }
         }
-    } else {
-        for (i, mut value) in (address.from..=address.to).zip(values.into_iter().cycle()) {
-            let offset = i * mif.width;
-            let index = offset / usize::BITS as usize;
-            let shift = offset % usize::BITS as usize;
 
-            value &= mask;
+        runs.into_iter()
+            .map(|(run, owner)| (run, self.spans[owner].clone()))
+            .collect()
+    }
+
+    /// The runs of addresses set by the same statement, with where that
+    /// statement is in `source`.
+    fn source_map(&self, source: &str) -> Vec<SourceSpan> {
+        // Statements are in file order, so each one is located from the
+        // previous one instead of from the start of the source.
+        let mut position = (0, 1, 1);
+        let locations = self
+            .spans
+            .iter()
+            .map(|span| {
+                let (offset, line, column) = position;
+                let (lines, columns) = location(&source[offset..], span.start - offset);
 
-            let (lower_chunk, overflow) = value.overflowing_shl(shift as u32);
-            mif.chunks[index] |= lower_chunk;
+                position = match lines {
+                    1 => (span.start, line, column + columns - 1),
+                    _ => (span.start, line + lines - 1, columns),
+                };
+                (position.1, position.2)
+            })
+            .collect::<Vec<_>>();
+        let mut runs: Vec<(Range<usize>, usize)> = Vec::new();
 
-            if overflow {
-                mif.chunks[index + 1] |= value >> (usize::BITS as usize - mask);
+        for (address, owner) in self.owners.iter().enumerate() {
+            let Some(owner) = *owner else { continue };
+
+            match runs.last_mut() {
+                Some((run, previous)) if *previous == owner && run.end == address => {
+                    run.end = address + 1;
+                }
+                _ => runs.push((address..address + 1, owner)),
             }
         }
+
+        runs.into_iter()
+            .map(|(run, owner)| {
+                let (line, column) = locations[owner];
+
+                SourceSpan::new(run, self.spans[owner].clone(), line, column)
+            })
+            .collect()
     }
+}
 
-    Ok((input, Element::Comment))
+/// Parses `source` without stopping at the first problem: after an error
+/// the parser skips to the next `;` and carries on, so every problem in the
+/// file is reported in one pass.
+///
+/// The document is `None` only when the header is incomplete. Otherwise it
+/// holds every entry that could be parsed.
+pub fn parse_recovering(source: impl AsRef<[u8]>) -> (Option<MifDocument>, Vec<Diagnostic>) {
+    parse_recovering_with(source, &ParseOptions::default())
 }
 
-fn content<'a>(input: &'a str, mif: &mut Mif) -> IResult<&'a str, ()> {
-    let foo = |input: &'a str| -> IResult<&'a str, Element> { data(input, mif) };
+/// Like [`parse_recovering`], with `options`.
+pub fn parse_recovering_with(
+    source: impl AsRef<[u8]>,
+    options: &ParseOptions,
+) -> (Option<MifDocument>, Vec<Diagnostic>) {
+    parse_text(&decode(source.as_ref()), options)
+}
 
-    let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, _) = tag("CONTENT")(input)?;
-    let (input, _) = many0_count(alt((singleline_comment, multiline_comment)))(input)?;
-    let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, _) = delimited(
-        tag("BEGIN"),
-        many0(alt((foo, singleline_comment, multiline_comment))),
-        tag("END"),
-    )(input)?;
-    let (input, _) = take_while(char::is_whitespace)(input)?;
-    let (input, _) = tag(";")(input)?;
+/// The byte order mark some editors put at the start of UTF-8 files.
+pub(crate) const BOM: char = '\u{FEFF}';
 
-    Ok((input, ()))
+/// Reads `bytes` as text with the same byte offsets, replacing every byte
+/// that isn't valid UTF-8 by `?`. Such bytes are only harmless in comments.
+pub(crate) fn decode(bytes: &[u8]) -> Cow<'_, str> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Cow::Borrowed(text),
+        Err(_) => Cow::Owned(
+            bytes
+                .utf8_chunks()
+                .flat_map(|chunk| {
+                    chunk
+                        .valid()
+                        .chars()
+                        .chain(std::iter::repeat_n('?', chunk.invalid().len()))
+                })
+                .collect(),
+        ),
+    }
 }
 
-#[wasm_bindgen(js_name = "parseMif")]
-pub fn parse_mif(input: &str) -> Option<Vec<u8>> {
-    let (input, elements) = many0(alt((
-        width,
-        depth,
-        address_radix,
-        data_radix,
-        multiline_comment,
-        singleline_comment,
-    )))(input)
-    .ok()?;
+fn parse_text(source: &str, options: &ParseOptions) -> (Option<MifDocument>, Vec<Diagnostic>) {
+    let mut diagnostics = Diagnostics {
+        source,
+        diagnostics: Vec::new(),
+        lines: None,
+    };
+    let mut builder = MifBuilder::default();
+    let mut input = skip_trivia(source);
 
-    let result = elements
-        .into_iter()
-        .fold(MifBuilder::default(), |mut acc, element| {
-            match element {
-                Element::DataRadix(radix) => acc.data_radix = Some(radix),
-                Element::AddressRadix(radix) => acc.address_radix = Some(radix),
-                Element::Width(width) => acc.width = Some(width),
-                Element::Depth(depth) => acc.depth = Some(depth),
-                _ => (),
+    while !input.is_empty() && tag_no_case::<_, _, Error>("CONTENT")(input).is_err() {
+        input = match attribute(input) {
+            Ok((input, element)) => {
+                builder.set(element);
+                input
             }
+            Err(error) => diagnostics.recover(error),
+        };
+        input = skip_trivia(input);
+    }
+
+    let missing = builder.missing();
+    let start = diagnostics.offset(input);
+    let content = start..start + input.len().min("CONTENT".len());
+
+    builder.defaults(options);
+
+    for header in missing {
+        let severity = match builder.missing().contains(&header) {
+            true => Severity::Error,
+            false => Severity::Warning,
+        };
+
+        diagnostics.push(severity, ErrorKind::MissingHeader(header), content.clone());
+    }
+
+    let Some(header) = builder.build() else {
+        return (None, diagnostics.diagnostics);
+    };
 
-            acc
-        });
-
-    let address_radix = result.address_radix.expect("Missing ADDRESS_RADIX");
-    let data_radix = result.data_radix.expect("Missing DATA_RADIX");
-    let width = result.width.expect("Missing WIDTH");
-    let depth = result.depth.expect("Missing DEPTH");
-    let size = width * depth / usize::BITS as usize;
-
-    let mut mif = Mif {
-        address_radix,
-        data_radix,
-        width,
-        depth,
-        chunks: Vec::with_capacity(size),
+    let mut image = Image {
+        mif: MifDocument::new(header),
+        owners: vec![None; header.depth],
+        spans: Vec::new(),
+    };
+    let severity = match options.overlap {
+        Overlap::Error => Severity::Error,
+        _ => Severity::Warning,
+    };
+
+    let begun = match begin(input) {
+        Ok((rest, _)) => {
+            input = rest;
+            true
+        }
+        Err(_) if input.is_empty() => {
+            let end = source.len();
+
+            diagnostics.push(Severity::Error, ErrorKind::MissingContent, end..end);
+            false
+        }
+        Err(error) => {
+            input = diagnostics.recover(error);
+            true
+        }
     };
 
-    mif.chunks.resize(size, 0);
+    // Comments up to here already annotate an entry.
+    let mut annotated = 0;
 
-    let (input, content) = content(input, &mut mif).ok()?;
+    loop {
+        let trivia = diagnostics.offset(input).max(annotated);
+        input = skip_trivia(input);
 
-    Some(
-        mif.chunks
+        let mut leading = &source[trivia..diagnostics.offset(input)];
+
+        if input.is_empty() {
+            // A missing `CONTENT BEGIN` was already reported.
+            if begun {
+                let end = source.len();
+                diagnostics.push(Severity::Error, ErrorKind::UnexpectedToken, end..end);
+            }
+
+            break;
+        }
+
+        match end(input) {
+            Ok((rest, _)) => {
+                input = rest;
+                break;
+            }
+            Err(error @ nom::Err::Failure(_)) => {
+                input = diagnostics.recover(error);
+                break;
+            }
+            Err(_) => (),
+        }
+
+        input = match data(input, &header, options.truncation) {
+            Ok((rest, (record, truncated))) => {
+                for value in truncated {
+                    let span = diagnostics.span(value);
+
+                    diagnostics.push(Severity::Warning, ErrorKind::ValueOutOfRange, span);
+                }
+
+                let start = diagnostics.offset(input);
+                let span = start..start + input[..input.len() - rest.len()].trim_end().len();
+
+                for (run, related) in image.assign(&record, span.clone(), options.overlap) {
+                    let lines = diagnostics.lines();
+                    let diagnostic =
+                        Diagnostic::new(severity, ErrorKind::Overlap(run), lines, span.clone())
+                            .with_related(lines, related);
+
+                    diagnostics.diagnostics.push(diagnostic);
+                }
+
+                while let Ok((rest, text)) = comment_text(leading) {
+                    image.mif.annotations.push(Annotation::new(
+                        record.addresses(),
+                        text,
+                        Placement::Leading,
+                    ));
+                    leading = rest;
+                }
+
+                let line = source[span.end..].trim_start_matches([' ', '\t']);
+
+                if let (true, Ok((rest, text))) = (line.starts_with(['-', '%']), comment_text(line))
+                {
+                    image.mif.annotations.push(Annotation::new(
+                        record.addresses(),
+                        text,
+                        Placement::Trailing,
+                    ));
+                    annotated = diagnostics.offset(rest);
+                }
+
+                rest
+            }
+            Err(error) => diagnostics.recover(error),
+        };
+    }
+
+    // Everything after `END;` that isn't a comment is reported as one
+    // problem, whether it is stray text or a whole second CONTENT block.
+    if let Err(nom::Err::Error(error) | nom::Err::Failure(error)) = trailing(input) {
+        let start = diagnostics.offset(error.input);
+
+        diagnostics.push(
+            Severity::Error,
+            error.kind,
+            start..start + error.input.trim_end().len(),
+        );
+    }
+
+    image.mif.fill(&options.fill);
+
+    if options.source_map {
+        image.mif.source_map = image.source_map(source);
+    }
+
+    (Some(image.mif), diagnostics.diagnostics)
+}
+
+/// Runs `parser` over the whole of `source`, turning a nom error into a
+/// [`MifError`] located in `source`.
+pub(crate) fn run<'a, T>(
+    source: &'a str,
+    parser: impl FnOnce(&'a str) -> IResult<'a, T>,
+) -> Result<(&'a str, T), MifError> {
+    match parser(source) {
+        Ok(result) => Ok(result),
+        Err(nom::Err::Error(error) | nom::Err::Failure(error)) => Err(MifError::new(
+            error.kind,
+            source,
+            source.len() - error.input.len(),
+        )),
+        Err(nom::Err::Incomplete(_)) => unreachable!(),
+    }
+}
+
+pub(crate) fn header_section<'a>(
+    source: &'a str,
+    options: &ParseOptions,
+) -> Result<(&'a str, MifHeader), MifError> {
+    run(source, |input| header(input, options))
+}
+
+pub(crate) fn is_content_start(input: &str) -> bool {
+    preceded(trivia, tag_no_case::<_, _, Error>("CONTENT"))(input).is_ok()
+}
+
+/// Parses `input`, failing on the first error.
+///
+/// `input` may be a `&str` or raw bytes. A leading byte order mark is
+/// skipped, and bytes that aren't valid UTF-8 are accepted in comments.
+pub fn parse(input: impl AsRef<[u8]>) -> Result<MifDocument, MifError> {
+    parse_with(input, &ParseOptions::default())
+}
+
+/// Like [`parse`], with `options`.
+pub fn parse_with(
+    input: impl AsRef<[u8]>,
+    options: &ParseOptions,
+) -> Result<MifDocument, MifError> {
+    let (mif, diagnostics) = parse_recovering_with(input, options);
+
+    match diagnostics
+        .iter()
+        .find(|diagnostic| diagnostic.severity() == Severity::Error)
+    {
+        Some(diagnostic) => Err(diagnostic.to_error()),
+        None => Ok(mif.expect("a document without errors has a header")),
+    }
+}
+
+#[wasm_bindgen(js_name = "tryParseMif")]
+pub fn try_parse_mif(
+    input: &str,
+    layout: Option<Layout>,
+    options: Option<ParseOptions>,
+) -> Result<Vec<u8>, MifError> {
+    try_parse_mif_bytes(input.as_bytes(), layout, options)
+}
+
+/// Like [`try_parse_mif`], for a file given as bytes, which may not be valid
+/// UTF-8.
+#[wasm_bindgen(js_name = "tryParseMifBytes")]
+pub fn try_parse_mif_bytes(
+    input: &[u8],
+    layout: Option<Layout>,
+    options: Option<ParseOptions>,
+) -> Result<Vec<u8>, MifError> {
+    parse_with(input, &options.unwrap_or_default())
+        .map(|mif| mif.to_bytes(layout.unwrap_or_default()))
+}
+
+#[wasm_bindgen(js_name = "parseMif")]
+pub fn parse_mif(
+    input: &str,
+    layout: Option<Layout>,
+    options: Option<ParseOptions>,
+) -> Option<Vec<u8>> {
+    try_parse_mif(input, layout, options).ok()
+}
+
+#[wasm_bindgen(js_name = "parseMifBytes")]
+pub fn parse_mif_bytes(
+    input: &[u8],
+    layout: Option<Layout>,
+    options: Option<ParseOptions>,
+) -> Option<Vec<u8>> {
+    try_parse_mif_bytes(input, layout, options).ok()
+}
+
+/// The outcome of [`parse_mif_recovering`]: the packed image, when the header
+/// was complete, and every diagnostic found.
+#[wasm_bindgen]
+pub struct Recovered {
+    bytes: Option<Vec<u8>>,
+    diagnostics: Vec<Diagnostic>,
+    annotations: Vec<Annotation>,
+    source_map: Vec<SourceSpan>,
+}
+
+#[wasm_bindgen]
+impl Recovered {
+    #[wasm_bindgen(getter)]
+    pub fn bytes(&self) -> Option<Vec<u8>> {
+        self.bytes.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.diagnostics.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn annotations(&self) -> Vec<Annotation> {
+        self.annotations.clone()
+    }
+
+    /// Empty unless `options.source_map` was set.
+    #[wasm_bindgen(getter, js_name = "sourceMap")]
+    pub fn source_map(&self) -> Vec<SourceSpan> {
+        self.source_map.clone()
+    }
+}
+
+/// Spans count UTF-16 code units, the way JS indexes `input`.
+#[wasm_bindgen(js_name = "parseMifRecovering")]
+pub fn parse_mif_recovering(
+    input: &str,
+    layout: Option<Layout>,
+    options: Option<ParseOptions>,
+) -> Recovered {
+    let recovered = parse_mif_recovering_bytes(input.as_bytes(), layout, options);
+    let offsets = Utf16::new(input);
+
+    Recovered {
+        diagnostics: recovered
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_utf16(&offsets))
+            .collect(),
+        source_map: recovered
+            .source_map
             .iter()
-            .flat_map(|byte| byte.to_ne_bytes())
+            .map(|source| source.to_utf16(&offsets))
             .collect(),
-    )
+        ..recovered
+    }
+}
+
+/// Spans are byte offsets into `input`.
+#[wasm_bindgen(js_name = "parseMifRecoveringBytes")]
+pub fn parse_mif_recovering_bytes(
+    input: &[u8],
+    layout: Option<Layout>,
+    options: Option<ParseOptions>,
+) -> Recovered {
+    let (mif, diagnostics) = parse_recovering_with(input, &options.unwrap_or_default());
+
+    Recovered {
+        bytes: mif
+            .as_ref()
+            .map(|mif| mif.to_bytes(layout.unwrap_or_default())),
+        diagnostics,
+        annotations: mif
+            .as_ref()
+            .map_or(Vec::new(), |mif| mif.annotations.clone()),
+        source_map: mif.map_or(Vec::new(), |mif| mif.source_map),
+    }
 }
 
+#[cfg(test)]
 mod test {
     use super::*;
 
@@ -372,8 +1304,533 @@ mod test {
         assert_eq!(number("1000", Radix::Uns), Ok(("", 1000)));
         assert_eq!(number("1000", Radix::Hex), Ok(("", 0x1000)));
         assert_eq!(number("fFdD", Radix::Hex), Ok(("", 0xFFDD)));
+        assert_eq!(number("1000", Radix::Dec), Ok(("", 1000)));
+    }
+
+    #[test]
+    pub fn values() {
+        assert_eq!(value("-1", Radix::Dec, 8), Ok(("", (vec![0xFF], false))));
+        assert_eq!(value("-128", Radix::Dec, 8), Ok(("", (vec![0x80], false))));
+        assert_eq!(value("127", Radix::Dec, 8), Ok(("", (vec![127], false))));
+        assert_eq!(
+            value("-1", Radix::Dec, 70),
+            Ok(("", (vec![u64::MAX, 0x3F], false)))
+        );
+        assert_eq!(
+            value("-590295810358705651712", Radix::Dec, 70),
+            Ok(("", (vec![0, 0x20], false)))
+        );
+        assert_eq!(value("-129", Radix::Dec, 8), Ok(("", (vec![0x7F], true))));
+        assert_eq!(value("1FF", Radix::Hex, 8), Ok(("", (vec![0xFF], true))));
+        assert_eq!(
+            value("123456789ABCDEF01", Radix::Hex, 16),
+            Ok(("", (vec![0xEF01], true)))
+        );
+        assert!(value("-1", Radix::Hex, 8).is_err());
+    }
+
+    #[test]
+    pub fn signed_data() {
+        let mif = parse(
+            "WIDTH=8;\nDEPTH=4;\nADDRESS_RADIX=DEC;\nDATA_RADIX=DEC;\nCONTENT BEGIN\n0: -1;\n1: -128 127;\n3: 200;\nEND;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            mif.words()
+                .map(|word| word.to_u64().unwrap())
+                .collect::<Vec<_>>(),
+            [0xFF, 0x80, 0x7F, 200]
+        );
+
+        let data = [-5i16, 0, 300, i16::MIN];
+        let mif = parse(crate::Mif::new(&data, Radix::Dec, Radix::Dec).to_string()).unwrap();
+
+        assert_eq!(
+            mif.words()
+                .map(|word| word.to_u64().unwrap())
+                .collect::<Vec<_>>(),
+            data.map(|value| value as u16 as u64)
+        );
     }
 
     #[test]
     pub fn mifs() {}
+
+    const HEADER: &str = "WIDTH=16;\nDEPTH=256;\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\n";
+
+    fn error(input: &str) -> (ErrorKind, usize, usize) {
+        let error = parse(input).unwrap_err();
+        (error.kind(), error.line(), error.column())
+    }
+
+    #[test]
+    pub fn errors() {
+        assert_eq!(
+            error("DEPTH=256;\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\nCONTENT BEGIN\nEND;"),
+            (ErrorKind::MissingHeader(Header::Width), 4, 1)
+        );
+        assert_eq!(
+            error(&format!("{HEADER}CONTENT BEGIN\n  0: 12G4;\nEND;")),
+            (ErrorKind::InvalidDigit(Radix::Hex), 6, 8)
+        );
+        assert_eq!(
+            error(&format!("{HEADER}CONTENT BEGIN\n[5..4]: 1;\nEND;")),
+            (ErrorKind::ReversedRange, 6, 1)
+        );
+        assert_eq!(
+            error(&format!("{HEADER}CONTENT BEGIN\n[0..100]: 1;\nEND;")),
+            (ErrorKind::AddressOutOfRange, 6, 1)
+        );
+        assert_eq!(
+            error(&format!("{HEADER}CONTENT BEGIN\nFF: 1 2;\nEND;")),
+            (ErrorKind::AddressOutOfRange, 6, 1)
+        );
+        assert_eq!(
+            error(&format!("{HEADER}CONTENT BEGIN\n0 = 1;\nEND;")),
+            (ErrorKind::UnexpectedToken, 6, 3)
+        );
+        assert_eq!(
+            error(&format!("{HEADER}CONTENT BEGIN\nEND;\n-- done\nEND;")),
+            (ErrorKind::TrailingInput, 8, 1)
+        );
+        assert_eq!(error("WIDTH=;"), (ErrorKind::UnexpectedToken, 1, 7));
+        assert_eq!(error(HEADER), (ErrorKind::MissingContent, 5, 1));
+    }
+
+    #[test]
+    pub fn valid() {
+        let bytes = try_parse_mif(
+            &format!("{HEADER}CONTENT BEGIN\n-- comment\n0: 1234;\n[1..3]: FFFF;\nEND;\n"),
+            None,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(bytes.len(), 16 * 256 / 8);
+        assert_eq!(
+            &bytes[..8],
+            [0x34, 0x12, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+    }
+
+    #[test]
+    pub fn recovering() {
+        let input = format!(
+            "{HEADER}FOO=1;\nCONTENT BEGIN\n0: 1;\n1: 2G;\n2 3;\n[3..4]: 5;\n300: 1;\n5: 6;\nEND;\n"
+        );
+        let (mif, diagnostics) = parse_recovering(&input);
+        let mif = mif.unwrap();
+        let found = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.kind(),
+                    &input[diagnostic.span()],
+                    diagnostic.line(),
+                    diagnostic.severity(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            found,
+            [
+                (ErrorKind::UnexpectedToken, "FOO=1;", 5, Severity::Error),
+                (
+                    ErrorKind::InvalidDigit(Radix::Hex),
+                    "G;",
+                    8,
+                    Severity::Error
+                ),
+                (ErrorKind::UnexpectedToken, "3;", 9, Severity::Error),
+                (ErrorKind::AddressOutOfRange, "300: 1;", 11, Severity::Error),
+            ]
+        );
+        assert_eq!(
+            (0..6)
+                .map(|address| mif.get(address).unwrap().to_u64().unwrap())
+                .collect::<Vec<_>>(),
+            [1, 0, 0, 5, 5, 6]
+        );
+        assert_eq!(parse(&input), Err(diagnostics[0].to_error()));
+    }
+
+    #[test]
+    pub fn recovering_headers() {
+        let (mif, diagnostics) = parse_recovering("WIDTH=8;\nCONTENT BEGIN\nEND;");
+
+        assert_eq!(mif, None);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.severity(), diagnostic.kind(), diagnostic.span()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    Severity::Warning,
+                    ErrorKind::MissingHeader(Header::AddressRadix),
+                    9..16
+                ),
+                (
+                    Severity::Warning,
+                    ErrorKind::MissingHeader(Header::DataRadix),
+                    9..16
+                ),
+                (
+                    Severity::Error,
+                    ErrorKind::MissingHeader(Header::Depth),
+                    9..16
+                ),
+            ]
+        );
+
+        let (mif, diagnostics) = parse_recovering(format!("{HEADER}CONTENT BEGIN\n0: 1;"));
+
+        assert!(mif.is_some());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].span(), 75..75);
+
+        let (mif, diagnostics) = parse_recovering(format!("{HEADER}-- no content\n"));
+
+        assert!(mif.is_some());
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.kind(), diagnostic.span()))
+                .collect::<Vec<_>>(),
+            [(ErrorKind::MissingContent, 70..70)]
+        );
+    }
+
+    #[test]
+    pub fn overlaps() {
+        let input = format!("{HEADER}CONTENT BEGIN\n[0..3]: 1;\n2: 2 3 4;\n3: 5;\nEND;\n");
+        let words = |overlap| {
+            let options = ParseOptions {
+                overlap,
+                ..Default::default()
+            };
+            let mif = parse_with(&input, &options).unwrap();
+
+            (0..5)
+                .map(|address| mif.get(address).unwrap().to_u64().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(words(Overlap::LastWins), [1, 1, 2, 5, 4]);
+        assert_eq!(words(Overlap::FirstWins), [1, 1, 1, 1, 4]);
+        assert_eq!(
+            parse_with(
+                &input,
+                &ParseOptions {
+                    overlap: Overlap::Error,
+                    ..Default::default()
+                }
+            )
+            .map_err(|error| (error.kind(), error.line())),
+            Err((ErrorKind::Overlap(Address { from: 2, to: 3 }), 7))
+        );
+
+        let (_, diagnostics) = parse_recovering(&input);
+        let found = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.severity(),
+                    diagnostic.kind(),
+                    &input[diagnostic.span()],
+                    &input[diagnostic.related().unwrap()],
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            found,
+            [
+                (
+                    Severity::Warning,
+                    ErrorKind::Overlap(Address { from: 2, to: 3 }),
+                    "2: 2 3 4;",
+                    "[0..3]: 1;"
+                ),
+                (
+                    Severity::Warning,
+                    ErrorKind::Overlap(Address { from: 3, to: 3 }),
+                    "3: 5;",
+                    "2: 2 3 4;"
+                ),
+            ]
+        );
+        assert_eq!(
+            diagnostics[1].to_string(),
+            "8:1: warning: address 3 assigned more than once (see line 7)"
+        );
+    }
+
+    #[test]
+    pub fn truncation() {
+        let input = format!("{HEADER}CONTENT BEGIN\n0: 1 1FFFF;\n2: 12345 2;\nEND;\n");
+        let options = |truncation| ParseOptions {
+            truncation,
+            ..Default::default()
+        };
+
+        let (mif, diagnostics) = parse_recovering_with(&input, &options(Truncation::Warning));
+        assert_eq!(mif.unwrap().get(2).unwrap().to_u64(), Some(0x2345));
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.severity(), &input[diagnostic.span()]))
+                .collect::<Vec<_>>(),
+            [(Severity::Warning, "1FFFF"), (Severity::Warning, "12345")]
+        );
+
+        let (mif, diagnostics) = parse_recovering_with(&input, &options(Truncation::Allow));
+        assert!(mif.is_some());
+        assert!(diagnostics.is_empty());
+
+        assert_eq!(
+            parse_with(&input, &options(Truncation::Error)).map_err(|error| (
+                error.kind(),
+                error.line(),
+                error.column()
+            )),
+            Err((ErrorKind::ValueOutOfRange, 6, 6))
+        );
+    }
+
+    #[test]
+    pub fn fill() {
+        let input = format!("{HEADER}CONTENT BEGIN\n1: 1;\n[3..4]: 2;\nEND;\n");
+        let words = |fill: &[Vec<u64>]| {
+            let options = ParseOptions {
+                fill: fill.to_vec(),
+                ..Default::default()
+            };
+            let mif = parse_with(&input, &options).unwrap();
+
+            assert!(!mif.is_initialized(0));
+            (0..7)
+                .map(|address| mif.get(address).unwrap().to_u64().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(words(&[]), [0, 1, 0, 2, 2, 0, 0]);
+        assert_eq!(
+            words(&[vec![u64::MAX]]),
+            [0xFFFF, 1, 0xFFFF, 2, 2, 0xFFFF, 0xFFFF]
+        );
+        assert_eq!(
+            words(&[vec![0xA], vec![0xB, 0xFF], vec![0xC]]),
+            [0xA, 1, 0xC, 2, 2, 0xC, 0xA]
+        );
+
+        let input = "WIDTH=72;\nDEPTH=5;\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n1: 1;\nEND;\n";
+        let words = |fill: &[Vec<u64>]| {
+            let options = ParseOptions {
+                fill: fill.to_vec(),
+                ..Default::default()
+            };
+            let mif = parse_with(input, &options).unwrap();
+
+            (0..5)
+                .map(|address| mif.get(address).unwrap().to_u128().unwrap())
+                .collect::<Vec<_>>()
+        };
+        let ones = (1 << 72) - 1;
+
+        assert_eq!(words(&[vec![u64::MAX; 2]]), [ones, 1, ones, ones, ones]);
+        assert_eq!(
+            words(&[vec![0xA, 0xB], vec![0xC], vec![0xD]]),
+            [
+                0xB_0000_0000_0000_000A,
+                1,
+                0xD,
+                0xB_0000_0000_0000_000A,
+                0xC
+            ]
+        );
+
+        let mut options = ParseOptions::new();
+        options.set_fill_limbs(vec![1, 2, 3, 4], 2);
+
+        assert_eq!(options.fill, [vec![1, 2], vec![3, 4]]);
+        assert_eq!(options.get_fill(), [1, 3]);
+    }
+
+    #[test]
+    pub fn defaults() {
+        let input = "WIDTH=8;\nCONTENT BEGIN\n10: 10;\nEND;";
+
+        assert_eq!(
+            parse(input).map_err(|error| error.kind()),
+            Err(ErrorKind::MissingHeader(Header::Depth))
+        );
+
+        let options = ParseOptions {
+            address_radix: Some(Radix::Uns),
+            depth: Some(16),
+            ..Default::default()
+        };
+        let mif = parse_with(input, &options).unwrap();
+
+        assert_eq!(
+            mif.header(),
+            MifHeader {
+                width: 8,
+                depth: 16,
+                address_radix: Radix::Uns,
+                data_radix: Radix::Hex,
+            }
+        );
+        assert_eq!(mif.get(10).unwrap().to_u64(), Some(0x10));
+
+        let options = ParseOptions {
+            strict: true,
+            ..options
+        };
+
+        assert_eq!(
+            parse_with(input, &options).map_err(|error| error.kind()),
+            Err(ErrorKind::MissingHeader(Header::AddressRadix))
+        );
+    }
+
+    #[test]
+    pub fn keywords() {
+        let mif = parse(
+            "-- students\ndata_radix = bin;\nDepth=4;\n% any order %\nwidth = 8;\naddress_radix = Uns;\ncontent\n-- between\n% both %\nbegin\n3: 101;\nend;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            mif.header(),
+            MifHeader {
+                width: 8,
+                depth: 4,
+                address_radix: Radix::Uns,
+                data_radix: Radix::Bin,
+            }
+        );
+        assert_eq!(mif.get(3).unwrap().to_u64(), Some(5));
+    }
+
+    #[test]
+    pub fn encodings() {
+        let expected = parse(format!("{HEADER}CONTENT BEGIN\n0: 1;\nEND;\n")).unwrap();
+        let mut bytes = b"\xEF\xBB\xBF-- caf\xE9\r\n".to_vec();
+
+        bytes.extend_from_slice(HEADER.replace('\n', "\r\n").as_bytes());
+        bytes.extend_from_slice(b"CONTENT\rBEGIN\r0: 1; -- \xFF\xFE\rEND;\r-- last");
+
+        let mif = parse(&bytes).unwrap();
+
+        assert_eq!(mif.header(), expected.header());
+        assert!(mif.words().eq(expected.words()));
+        assert_eq!(mif.annotations()[0].text(), "??");
+        assert_eq!(
+            try_parse_mif_bytes(&bytes, None, None),
+            Ok(expected.to_bytes(Layout::default()))
+        );
+
+        bytes.extend_from_slice(b"\n\xE9: 1;");
+
+        let error = parse(&bytes).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::TrailingInput);
+        assert_eq!((error.line(), error.column()), (11, 1));
+        assert_eq!(&bytes[error.offset()..error.offset() + 1], b"\xE9");
+        assert_eq!(
+            parse("\u{FEFF}WIDTH=;").map_err(|error| (error.line(), error.column())),
+            Err((1, 7))
+        );
+    }
+
+    #[test]
+    pub fn utf16() {
+        let input = format!(
+            "-- \u{1F600}\n{HEADER}CONTENT BEGIN\n0: 1; -- caf\u{E9}\n0: 2;\n1: G;\nEND;\n"
+        );
+        let options = ParseOptions {
+            source_map: true,
+            ..Default::default()
+        };
+        let recovered = parse_mif_recovering(&input, None, Some(options));
+        let units = input.encode_utf16().collect::<Vec<_>>();
+        let text = |span: Range<usize>| String::from_utf16(&units[span]).unwrap();
+
+        assert_eq!(
+            recovered
+                .diagnostics()
+                .iter()
+                .map(|diagnostic| (text(diagnostic.span()), diagnostic.related().map(text)))
+                .collect::<Vec<_>>(),
+            [
+                ("0: 2;".to_owned(), Some("0: 1;".to_owned())),
+                ("G;".to_owned(), None)
+            ]
+        );
+        assert_eq!(text(recovered.source_map()[0].span()), "0: 2;");
+    }
+
+    #[test]
+    pub fn trailing_content() {
+        let input = format!(
+            "{HEADER}CONTENT BEGIN\n0: 1;\nEND;\n-- fine\nCONTENT BEGIN\n1: 2;\nEND;\n-- also fine\n"
+        );
+        let (mif, diagnostics) = parse_recovering(&input);
+
+        assert_eq!(mif.unwrap().get(1).unwrap().to_u64(), Some(0));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind(), ErrorKind::TrailingInput);
+        assert_eq!(
+            &input[diagnostics[0].span()],
+            "CONTENT BEGIN\n1: 2;\nEND;\n-- also fine"
+        );
+
+        let (_, diagnostics) = parse_recovering(format!("{HEADER}CONTENT BEGIN\nEND; garbage"));
+
+        assert_eq!(diagnostics[0].line(), 6);
+        assert_eq!(diagnostics[0].column(), 6);
+        assert!(parse(format!("{HEADER}CONTENT BEGIN\nEND; % c %\n-- c")).is_ok());
+    }
+
+    #[test]
+    pub fn source_map() {
+        let input =
+            format!("{HEADER}CONTENT BEGIN\n[0..3]: 1;\n  2: 5 6; 9: 1;\r\n[4..5]: 7;\nEND;");
+        let options = ParseOptions {
+            source_map: true,
+            ..ParseOptions::default()
+        };
+        let mif = parse_with(&input, &options).unwrap();
+        let map = mif
+            .source_map()
+            .iter()
+            .map(|source| {
+                (
+                    source.addresses(),
+                    &input[source.span()],
+                    source.line(),
+                    source.column(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            map,
+            [
+                (0..2, "[0..3]: 1;", 6, 1),
+                (2..4, "2: 5 6;", 7, 3),
+                (4..6, "[4..5]: 7;", 8, 1),
+                (9..10, "9: 1;", 7, 11),
+            ]
+        );
+        assert_eq!(mif.source_of(1).unwrap().line(), 6);
+        assert_eq!(mif.source_of(3).unwrap().line(), 7);
+        assert_eq!(mif.source_of(7), None);
+        assert_eq!(mif.source_of(9).unwrap().span(), mif.source_map()[3].span());
+        assert!(parse(&input).unwrap().source_map().is_empty());
+    }
 }
@@ -0,0 +1,388 @@
+use std::io::BufRead;
+
+use crate::{
+    error::{location, ErrorKind, MifError},
+    parser::{self, MifHeader, ParseOptions, Record, Terminator, Truncation},
+};
+
+/// Parses a MIF from any [`BufRead`] one statement at a time, so memory use
+/// is bounded by the longest statement instead of the whole file.
+///
+/// [`StreamParser::new`] reads everything up to `CONTENT BEGIN`; iterating
+/// then yields each data entry in file order. Iteration stops after `END;`
+/// and the rest of the input has been checked, or after the first error.
+pub struct StreamParser<R> {
+    statements: Statements<R>,
+    header: MifHeader,
+    truncation: Truncation,
+    state: State,
+    position: Position,
+    pending: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+    Content,
+    Trailing,
+    Done,
+}
+
+impl<R: BufRead> StreamParser<R> {
+    pub fn new(reader: R) -> Result<Self, MifError> {
+        Self::with_options(reader, &ParseOptions::default())
+    }
+
+    /// Like [`StreamParser::new`], taking missing header attributes from
+    /// `options`. A value wider than `WIDTH` is an error with
+    /// [`Truncation::Error`] and otherwise silently truncated. Entries are
+    /// yielded as written, so the other options don't apply.
+    pub fn with_options(reader: R, options: &ParseOptions) -> Result<Self, MifError> {
+        let mut statements = Statements {
+            reader,
+            buffer: Vec::new(),
+        };
+        let mut text = String::new();
+
+        while let Some(statement) = statements.read(Position::START.advance(&text))? {
+            text.push_str(&statement);
+
+            if parser::is_content_start(&statement) {
+                break;
+            }
+        }
+
+        let (rest, header) = parser::header_section(&text, options)?;
+
+        Ok(Self {
+            statements,
+            header,
+            truncation: options.truncation,
+            state: State::Content,
+            position: Position::START.advance(&text[..text.len() - rest.len()]),
+            pending: Some(rest.to_owned()),
+        })
+    }
+
+    pub fn header(&self) -> MifHeader {
+        self.header
+    }
+
+    fn next_statement(&mut self) -> Result<Option<String>, MifError> {
+        match self.pending.take() {
+            Some(text) => Ok(Some(text)),
+            None => self.statements.read(self.position),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for StreamParser<R> {
+    type Item = Result<Record, MifError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.state != State::Done {
+            let error = match (self.state, self.next_statement()) {
+                (_, Err(error)) => error,
+                (State::Content, Ok(None)) => self.position.error(ErrorKind::UnexpectedToken),
+                (State::Trailing, Ok(None)) => {
+                    self.state = State::Done;
+                    return None;
+                }
+                (State::Content, Ok(Some(text))) => {
+                    match parser::run(&text, |input| {
+                        parser::statement(input, &self.header, self.truncation)
+                    }) {
+                        Ok((_, record)) => {
+                            self.position = self.position.advance(&text);
+                            self.statements.recycle(text);
+
+                            match record {
+                                Some(record) => return Some(Ok(record)),
+                                None => self.state = State::Trailing,
+                            }
+
+                            continue;
+                        }
+                        Err(error) => self.position.relocate(error),
+                    }
+                }
+                (State::Trailing, Ok(Some(text))) => match parser::run(&text, parser::trailing) {
+                    Ok(_) => {
+                        self.position = self.position.advance(&text);
+                        self.statements.recycle(text);
+                        continue;
+                    }
+                    Err(error) => self.position.relocate(error),
+                },
+                (State::Done, _) => unreachable!(),
+            };
+
+            self.state = State::Done;
+            return Some(Err(error));
+        }
+
+        None
+    }
+}
+
+/// Where a statement starts in the whole input.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Position {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Position {
+    const START: Self = Self {
+        offset: 0,
+        line: 1,
+        column: 1,
+    };
+
+    /// The position right after `text`, read from here.
+    fn advance(self, text: &str) -> Self {
+        let (line, column) = location(text, text.len());
+
+        self.offset_by(text.len(), line, column)
+    }
+
+    /// Moves an error located in a statement starting here to its place in
+    /// the whole input.
+    fn relocate(self, error: MifError) -> MifError {
+        let position = self.offset_by(error.offset(), error.line(), error.column());
+
+        MifError::at(
+            error.kind(),
+            position.offset,
+            position.line,
+            position.column,
+        )
+    }
+
+    fn offset_by(self, offset: usize, line: usize, column: usize) -> Self {
+        Self {
+            offset: self.offset + offset,
+            line: self.line + line - 1,
+            column: match line {
+                1 => self.column + column - 1,
+                _ => column,
+            },
+        }
+    }
+
+    fn error(self, kind: ErrorKind) -> MifError {
+        MifError::at(kind, self.offset, self.line, self.column)
+    }
+}
+
+/// Splits the input into statements, each running up to and including the
+/// next `;` that isn't inside a comment, or up to the end of the input.
+struct Statements<R> {
+    reader: R,
+    buffer: Vec<u8>,
+}
+
+impl<R: BufRead> Statements<R> {
+    fn read(&mut self, position: Position) -> Result<Option<String>, MifError> {
+        let mut bytes = std::mem::take(&mut self.buffer);
+        let mut terminator = Terminator::default();
+
+        bytes.clear();
+
+        loop {
+            let buffer = match self.reader.fill_buf() {
+                Ok(buffer) => buffer,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(position.error(ErrorKind::Io(error.kind()))),
+            };
+
+            if buffer.is_empty() {
+                break;
+            }
+
+            let end = terminator.find(buffer);
+            let used = end.map_or(buffer.len(), |i| i + 1);
+
+            bytes.extend_from_slice(&buffer[..used]);
+            self.reader.consume(used);
+
+            if end.is_some() {
+                break;
+            }
+        }
+
+        if bytes.is_empty() {
+            self.buffer = bytes;
+            return Ok(None);
+        }
+
+        Ok(Some(String::from_utf8(bytes).unwrap_or_else(|error| {
+            parser::decode(error.as_bytes()).into_owned()
+        })))
+    }
+
+    /// Hands a statement's allocation back for the next read.
+    fn recycle(&mut self, text: String) {
+        self.buffer = text.into_bytes();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::BufReader;
+
+    use crate::{parser::parse, Radix};
+
+    use super::*;
+
+    const MIF: &str = "-- generated; do not edit\nWIDTH=8;\nDEPTH=16;\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\nCONTENT\nBEGIN\n0: 1 2 3;\n% a; b %\n[4..7]: FF;\nA: 10;\nEND;\n-- done\n";
+
+    fn stream(input: &str) -> StreamParser<BufReader<&[u8]>> {
+        StreamParser::new(BufReader::with_capacity(3, input.as_bytes())).unwrap()
+    }
+
+    #[test]
+    pub fn records() {
+        let parser = stream(MIF);
+
+        assert_eq!(
+            parser.header(),
+            MifHeader {
+                width: 8,
+                depth: 16,
+                address_radix: Radix::Hex,
+                data_radix: Radix::Hex,
+            }
+        );
+
+        let records = parser.collect::<Result<Vec<_>, _>>().unwrap();
+        let assignments = records
+            .iter()
+            .flat_map(|record| record.assignments())
+            .map(|(address, word)| (address, word.to_u64().unwrap()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            records[1].address(),
+            crate::parser::Address { from: 4, to: 7 }
+        );
+        assert_eq!(
+            assignments,
+            [
+                (0, 1),
+                (1, 2),
+                (2, 3),
+                (4, 0xFF),
+                (5, 0xFF),
+                (6, 0xFF),
+                (7, 0xFF),
+                (10, 0x10)
+            ]
+        );
+
+        let mif = parse(MIF).unwrap();
+
+        for (address, word) in assignments {
+            assert_eq!(mif.get(address).unwrap().to_u64(), Some(word));
+        }
+    }
+
+    #[test]
+    pub fn errors() {
+        let error = |input: &str| {
+            let error = match StreamParser::new(input.as_bytes()) {
+                Ok(parser) => parser.filter_map(Result::err).next().unwrap(),
+                Err(error) => error,
+            };
+
+            (error.kind(), error.offset(), error.line(), error.column())
+        };
+        let expected = |input: &str| {
+            let error = parse(input).unwrap_err();
+
+            (error.kind(), error.offset(), error.line(), error.column())
+        };
+
+        for input in [
+            "WIDTH=8;\nADDRESS_RADIX=HEX;\nCONTENT BEGIN\nEND;",
+            "WIDTH=8;\nDEPTH=4;\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\n-- no content\n",
+            "WIDTH=8;\nDEPTH=4;\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n0: 1;\n  4: 1;\nEND;",
+            "WIDTH=8;\nDEPTH=4;\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n0: 1;\n1: 1G;\nEND;",
+            "WIDTH=8;\nDEPTH=4;\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n0: 1;",
+            "WIDTH=8;\nDEPTH=4;\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\nCONTENT BEGIN\nEND;\n-- ok\n0: 1;",
+        ] {
+            assert_eq!(error(input), expected(input), "{input}");
+        }
+    }
+
+    #[test]
+    pub fn truncation() {
+        let input = "WIDTH=8;\nDEPTH=4;\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n0: 1;\n1: 1FF;\nEND;";
+        let options = ParseOptions {
+            truncation: Truncation::Error,
+            ..ParseOptions::default()
+        };
+        let error = StreamParser::with_options(input.as_bytes(), &options)
+            .unwrap()
+            .find_map(Result::err)
+            .unwrap();
+
+        assert_eq!(error.kind(), ErrorKind::ValueOutOfRange);
+        assert_eq!(Err(error), parser::parse_with(input, &options));
+
+        let words = StreamParser::new(input.as_bytes())
+            .unwrap()
+            .map(|record| record.unwrap().values().next().unwrap().to_u64())
+            .collect::<Vec<_>>();
+
+        assert_eq!(words, [Some(1), Some(0xFF)]);
+    }
+
+    #[test]
+    pub fn large() {
+        let depth = 1 << 14;
+        let mut input = format!(
+            "WIDTH=32;\nDEPTH={depth};\nADDRESS_RADIX=UNS;\nDATA_RADIX=UNS;\nCONTENT BEGIN\n"
+        );
+
+        for address in 0..depth {
+            input.push_str(&format!("{address}: {};\n", address * 3));
+        }
+
+        input.push_str("END;\n");
+
+        let mut count = 0;
+
+        for (i, record) in stream(&input).enumerate() {
+            let record = record.unwrap();
+
+            assert_eq!(record.address().from, i);
+            assert_eq!(record.values().next().unwrap().to_u64(), Some(i as u64 * 3));
+            count += 1;
+        }
+
+        assert_eq!(count, depth);
+    }
+
+    #[test]
+    pub fn encodings() {
+        let input = b"\xEF\xBB\xBF-- caf\xE9\rWIDTH=8;\r\nDEPTH=4;\rADDRESS_RADIX=HEX;\rDATA_RADIX=HEX;\rCONTENT BEGIN\r0: 1; -- \xFF\r1: 2;\rEND;\r-- last";
+        let records = StreamParser::new(&input[..])
+            .unwrap()
+            .map(|record| record.unwrap().address().from)
+            .collect::<Vec<_>>();
+
+        assert_eq!(records, [0, 1]);
+
+        let mut input = input.to_vec();
+        input.extend_from_slice(b"\r\xE9;");
+
+        let error = StreamParser::new(&input[..])
+            .unwrap()
+            .find_map(Result::err)
+            .unwrap();
+
+        assert_eq!(Err(error), parse(&input));
+    }
+}
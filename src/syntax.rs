@@ -0,0 +1,892 @@
+use std::{fmt::Display, ops::Range};
+
+use crate::{
+    document::MifDocument,
+    error::{ErrorKind, MifError},
+    parser::{self, Address, Element, Header, MifHeader, Terminator},
+    word::{self, Word},
+    Radix,
+};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Kind {
+    /// An attribute, with the span of its value in the statement.
+    Header {
+        header: Header,
+        value: Range<usize>,
+    },
+    Begin,
+    /// A data entry, with the span of its address in the statement and
+    /// how many values it has.
+    Entry {
+        address: Address,
+        span: Range<usize>,
+        values: usize,
+    },
+    End,
+}
+
+/// A statement plus everything around it: the comments and whitespace
+/// before it, and the rest of its line.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Node {
+    leading: String,
+    text: String,
+    trailing: String,
+    kind: Kind,
+}
+
+impl Node {
+    /// Spans of the values of a data entry.
+    fn values(&self) -> Vec<Range<usize>> {
+        match &self.kind {
+            Kind::Entry { span, .. } => values(&self.text, span),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The line break ending this node's line, if it has one.
+    fn newline(&self) -> Option<&str> {
+        ["\r\n", "\n", "\r"]
+            .into_iter()
+            .find(|newline| self.trailing.ends_with(newline))
+    }
+
+    /// Indentation of the statement, if it starts its own line.
+    fn indent(&self) -> Option<&str> {
+        let indent = &self.leading[self.leading.rfind(['\n', '\r']).map_or(0, |i| i + 1)..];
+
+        indent.chars().all(char::is_whitespace).then_some(indent)
+    }
+
+    fn replace(&mut self, mut edits: Vec<(Range<usize>, String)>) {
+        edits.sort_by_key(|(range, _)| range.start);
+
+        for (range, text) in edits.into_iter().rev() {
+            self.text.replace_range(range, &text);
+        }
+    }
+}
+
+/// How new entries are written, copied from an existing one.
+struct Style {
+    indent: String,
+    separator: String,
+    address: String,
+    value: String,
+    newline: String,
+}
+
+/// A lossless syntax tree of a MIF file.
+///
+/// Every byte of the source, comments and whitespace included, is kept, so
+/// the tree prints back exactly as it was parsed. Edits only rewrite the
+/// statements they touch, leaving every other line as it was.
+#[derive(Clone, Debug)]
+pub struct SyntaxTree {
+    header: MifHeader,
+    nodes: Vec<Node>,
+    tail: String,
+    /// The document validating the source parsed into, until an edit.
+    document: Option<MifDocument>,
+}
+
+impl PartialEq for SyntaxTree {
+    fn eq(&self, other: &Self) -> bool {
+        self.header == other.header && self.nodes == other.nodes && self.tail == other.tail
+    }
+}
+
+impl Eq for SyntaxTree {}
+
+impl SyntaxTree {
+    /// Parses `source`, which must be a valid MIF file.
+    pub fn parse(source: &str) -> Result<Self, MifError> {
+        let document = parser::parse(source)?;
+        let header = document.header();
+        let mut nodes = Vec::new();
+        let mut input = source;
+        let mut content = false;
+
+        loop {
+            let rest = parser::skip_trivia(input);
+            let leading = &input[..input.len() - rest.len()];
+            let (text, kind) = statement(rest, &header, content);
+            let trailing = &rest[text.len()..][..line_end(&rest[text.len()..])];
+
+            content |= kind == Kind::Begin;
+
+            nodes.push(Node {
+                leading: leading.to_string(),
+                text: text.to_string(),
+                trailing: trailing.to_string(),
+                kind,
+            });
+            input = &rest[text.len() + trailing.len()..];
+
+            if nodes.last().is_some_and(|node| node.kind == Kind::End) {
+                break;
+            }
+        }
+
+        Ok(Self {
+            header,
+            nodes,
+            tail: input.to_string(),
+            document: Some(document),
+        })
+    }
+
+    pub fn header(&self) -> MifHeader {
+        self.header
+    }
+
+    /// The document described by the tree as it is now.
+    pub fn document(&self) -> MifDocument {
+        match &self.document {
+            Some(document) => document.clone(),
+            None => parser::parse(self.to_string()).expect("edits keep the tree valid"),
+        }
+    }
+
+    /// The address of every data entry, in the order they are written.
+    pub fn entries(&self) -> impl Iterator<Item = Address> + '_ {
+        self.nodes.iter().filter_map(|node| match node.kind {
+            Kind::Entry { address, .. } => Some(address),
+            _ => None,
+        })
+    }
+
+    /// Sets the word at `address` to `value`, given as little-endian limbs.
+    ///
+    /// The value is rewritten in place in every entry assigning `address`.
+    /// A range is split around `address`, and an address that no entry
+    /// assigns gets a new entry.
+    pub fn set_word(&mut self, address: usize, value: &[u64]) -> Result<(), ErrorKind> {
+        if address >= self.header.depth {
+            return Err(ErrorKind::AddressOutOfRange);
+        }
+
+        let value = self.fit(value)?;
+        let covering = (0..self.nodes.len())
+            .filter(|&index| self.covers(index, address))
+            .collect::<Vec<_>>();
+
+        if covering.is_empty() {
+            return self.insert_entry(
+                Address {
+                    from: address,
+                    to: address,
+                },
+                &[&value],
+            );
+        }
+
+        for index in covering.into_iter().rev() {
+            self.set_in_entry(index, address, &value);
+        }
+
+        Ok(())
+    }
+
+    /// Adds an entry assigning `values` to `address`, placed before the
+    /// first entry that starts at a higher address.
+    pub fn insert_entry(&mut self, address: Address, values: &[&[u64]]) -> Result<(), ErrorKind> {
+        let Address { from, to } = address;
+
+        if values.is_empty() {
+            return Err(ErrorKind::UnexpectedToken);
+        }
+
+        if from > to {
+            return Err(ErrorKind::ReversedRange);
+        }
+
+        if to >= self.header.depth || (from == to && from + values.len() > self.header.depth) {
+            return Err(ErrorKind::AddressOutOfRange);
+        }
+
+        let values = values
+            .iter()
+            .map(|value| self.fit(value))
+            .collect::<Result<Vec<_>, _>>()?;
+        let position = self
+            .nodes
+            .iter()
+            .position(|node| match node.kind {
+                Kind::Entry { address, .. } => address.from > from,
+                Kind::End => true,
+                _ => false,
+            })
+            .expect("a valid tree has END");
+        let near = (0..position)
+            .rev()
+            .chain(position..self.nodes.len())
+            .find(|&index| matches!(self.nodes[index].kind, Kind::Entry { .. }));
+        let style = self.style(near);
+        let values = values
+            .iter()
+            .map(|value| self.format_value(value, &style.value))
+            .collect::<Vec<_>>();
+        let text = self.render(&style, address, &values);
+        let node = self.node(style.indent.clone(), text, style.newline.clone());
+
+        self.nodes.insert(position, node);
+        self.document = None;
+
+        Ok(())
+    }
+
+    /// Removes the `index`th data entry with the rest of its line, keeping
+    /// the comments above it. Returns its address.
+    pub fn remove_entry(&mut self, index: usize) -> Option<Address> {
+        let position = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| matches!(node.kind, Kind::Entry { .. }))
+            .nth(index)?
+            .0;
+        let node = self.nodes.remove(position);
+        self.document = None;
+        let kept = match (node.indent(), node.newline()) {
+            (Some(indent), Some(_)) => &node.leading[..node.leading.len() - indent.len()],
+            _ => &node.leading,
+        };
+
+        self.nodes[position].leading.insert_str(0, kept);
+
+        match node.kind {
+            Kind::Entry { address, .. } => Some(address),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Changes `WIDTH`, failing if a value written in the file doesn't fit.
+    pub fn set_width(&mut self, width: usize) -> Result<(), ErrorKind> {
+        let radix = self.header.data_radix;
+        let fits = self.nodes.iter().all(|node| {
+            node.values()
+                .into_iter()
+                .all(|value| fits(&node.text[value], radix, width))
+        });
+
+        if !fits {
+            return Err(ErrorKind::ValueOutOfRange);
+        }
+
+        self.header.width = width;
+        self.set_header(Header::Width, width.to_string());
+
+        Ok(())
+    }
+
+    /// Changes `DEPTH`, failing if an entry assigns an address past it.
+    pub fn set_depth(&mut self, depth: usize) -> Result<(), ErrorKind> {
+        let fits = self.nodes.iter().all(|node| match node.kind {
+            Kind::Entry {
+                address, values, ..
+            } if address.from == address.to => address.from + values <= depth,
+            Kind::Entry { address, .. } => address.to < depth,
+            _ => true,
+        });
+
+        if !fits {
+            return Err(ErrorKind::AddressOutOfRange);
+        }
+
+        self.header.depth = depth;
+        self.set_header(Header::Depth, depth.to_string());
+
+        Ok(())
+    }
+
+    /// Changes `ADDRESS_RADIX`, rewriting every address in the new radix.
+    pub fn set_address_radix(&mut self, radix: Radix) {
+        let old = self.header.address_radix;
+
+        if old == radix {
+            return;
+        }
+
+        self.header.address_radix = radix;
+
+        for index in 0..self.nodes.len() {
+            let node = &self.nodes[index];
+            let Kind::Entry { span, .. } = &node.kind else {
+                continue;
+            };
+            let edits = tokens(&node.text, span.clone(), |c| c.is_ascii_alphanumeric())
+                .into_iter()
+                .map(|range| {
+                    let number = usize::from_str_radix(&node.text[range.clone()], old.radix())
+                        .expect("addresses were validated");
+
+                    (
+                        range,
+                        format_word(address_word(&[number as u64]), radix, false, None),
+                    )
+                })
+                .collect();
+            let mut node = node.clone();
+
+            node.replace(edits);
+            self.nodes[index] = self.node(node.leading, node.text, node.trailing);
+        }
+
+        self.set_header(Header::AddressRadix, radix.to_string());
+    }
+
+    /// Changes `DATA_RADIX`, rewriting every value in the new radix.
+    /// Negative `DEC` values stay negative if the new radix is `DEC`.
+    pub fn set_data_radix(&mut self, radix: Radix) {
+        let old = self.header.data_radix;
+
+        if old == radix {
+            return;
+        }
+
+        let width = self.header.width;
+
+        for node in self.nodes.iter_mut() {
+            let edits = node
+                .values()
+                .into_iter()
+                .map(|range| {
+                    let token = &node.text[range.clone()];
+                    let (_, (value, _)) =
+                        parser::value(token, old, width).expect("values were validated");
+                    let signed = token.starts_with('-');
+
+                    (
+                        range,
+                        format_word(Word::new(&value, width), radix, signed, None),
+                    )
+                })
+                .collect();
+
+            node.replace(edits);
+        }
+
+        self.header.data_radix = radix;
+        self.set_header(Header::DataRadix, radix.to_string());
+    }
+
+    /// Sets the value of every `header` attribute, adding one before
+    /// `CONTENT` if the file left it to its default.
+    fn set_header(&mut self, header: Header, text: String) {
+        let mut found = false;
+        self.document = None;
+
+        for node in self.nodes.iter_mut() {
+            if let Kind::Header { header: h, value } = &mut node.kind {
+                if *h == header {
+                    node.text.replace_range(value.clone(), &text);
+                    *value = value.start..value.start + text.len();
+                    found = true;
+                }
+            }
+        }
+
+        if found {
+            return;
+        }
+
+        let begin = self
+            .nodes
+            .iter()
+            .position(|node| node.kind == Kind::Begin)
+            .expect("a valid tree has CONTENT");
+        let text = format!("{header}={text};");
+        let (_, kind) = statement(&text, &self.header, false);
+        let newline = self.nodes[..begin]
+            .iter()
+            .find_map(Node::newline)
+            .unwrap_or("\n")
+            .to_string();
+        let leading = std::mem::take(&mut self.nodes[begin].leading);
+
+        self.nodes[begin].leading = leading.trim_start().to_string();
+        self.nodes.insert(
+            begin,
+            Node {
+                leading: leading[..leading.len() - leading.trim_start().len()].to_string(),
+                text,
+                trailing: newline,
+                kind,
+            },
+        );
+    }
+
+    /// Whether the node at `index` is an entry assigning `address`.
+    fn covers(&self, index: usize, address: usize) -> bool {
+        let node = &self.nodes[index];
+
+        match node.kind {
+            Kind::Entry {
+                address: entry,
+                values,
+                ..
+            } if entry.from == entry.to => (entry.from..entry.from + values).contains(&address),
+            Kind::Entry { address: entry, .. } => (entry.from..=entry.to).contains(&address),
+            _ => false,
+        }
+    }
+
+    fn set_in_entry(&mut self, index: usize, address: usize, value: &[u64]) {
+        let node = &self.nodes[index];
+        let Kind::Entry { address: entry, .. } = node.kind else {
+            unreachable!()
+        };
+        let values = node.values();
+        let offset = address - entry.from;
+        let current = &node.text[values[offset % values.len()].clone()];
+
+        if self.parse_value(current) == value {
+            return;
+        }
+
+        self.document = None;
+
+        let new = self.format_value(value, current);
+
+        if entry.from == entry.to {
+            self.nodes[index].replace(vec![(values[offset].clone(), new)]);
+            return;
+        }
+
+        let style = self.style(Some(index));
+        let texts = values
+            .iter()
+            .map(|range| node.text[range.clone()].to_string())
+            .collect::<Vec<_>>();
+        let piece = |from: usize, to: usize, skip: usize| {
+            let values = texts
+                .iter()
+                .cycle()
+                .skip(skip)
+                .take((to - from + 1).min(texts.len()))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            self.render(&style, Address { from, to }, &values)
+        };
+        let mut pieces = Vec::new();
+
+        if offset > 0 {
+            pieces.push(piece(entry.from, address - 1, 0));
+        }
+
+        pieces.push(self.render(
+            &style,
+            Address {
+                from: address,
+                to: address,
+            },
+            &[new],
+        ));
+
+        if address < entry.to {
+            pieces.push(piece(address + 1, entry.to, (offset + 1) % texts.len()));
+        }
+
+        let last = pieces.len() - 1;
+        let has_newline = node.newline().is_some();
+        let nodes = pieces
+            .into_iter()
+            .enumerate()
+            .map(|(i, text)| {
+                let leading = match i {
+                    0 => node.leading.clone(),
+                    _ => style.indent.clone(),
+                };
+                let trailing = match (has_newline, i == 0, i == last) {
+                    (true, true, _) | (false, _, true) => node.trailing.clone(),
+                    _ => style.newline.clone(),
+                };
+
+                self.node(leading, text, trailing)
+            })
+            .collect::<Vec<_>>();
+
+        self.nodes.splice(index..=index, nodes);
+    }
+
+    /// Builds the node of a data entry from its text.
+    fn node(&self, leading: String, text: String, trailing: String) -> Node {
+        let (_, kind) = statement(&text, &self.header, true);
+
+        Node {
+            leading,
+            text,
+            trailing,
+            kind,
+        }
+    }
+
+    /// The style of the entry at `index`, or a plain one.
+    fn style(&self, index: Option<usize>) -> Style {
+        let mut style = Style {
+            indent: String::new(),
+            separator: ": ".to_string(),
+            address: String::new(),
+            value: String::new(),
+            newline: "\n".to_string(),
+        };
+        let Some(node) = index.map(|index| &self.nodes[index]) else {
+            return style;
+        };
+        let Kind::Entry { span, .. } = &node.kind else {
+            return style;
+        };
+        let values = node.values();
+
+        style.indent = node.indent().unwrap_or_default().to_string();
+        style.separator = node.text[span.end..values[0].start].to_string();
+        style.address = tokens(&node.text, span.clone(), |c| c.is_ascii_alphanumeric())
+            .first()
+            .map_or(String::new(), |range| node.text[range.clone()].to_string());
+        style.value = node.text[values[0].clone()].to_string();
+        style.newline = node.newline().unwrap_or("\n").to_string();
+
+        style
+    }
+
+    fn render(&self, style: &Style, address: Address, values: &[String]) -> String {
+        let number = |number: usize| {
+            format_word(
+                address_word(&[number as u64]),
+                self.header.address_radix,
+                false,
+                Some(&style.address),
+            )
+        };
+        let address = match address.from == address.to {
+            true => number(address.from),
+            false => format!("[{}..{}]", number(address.from), number(address.to)),
+        };
+
+        format!("{address}{}{};", style.separator, values.join(" "))
+    }
+
+    fn format_value(&self, value: &[u64], like: &str) -> String {
+        format_word(
+            Word::new(value, self.header.width),
+            self.header.data_radix,
+            like.starts_with('-'),
+            Some(like),
+        )
+    }
+
+    fn parse_value(&self, token: &str) -> Vec<u64> {
+        parser::value(token, self.header.data_radix, self.header.width)
+            .expect("values were validated")
+            .1
+             .0
+    }
+
+    /// Extends `value` to the limbs of a word, checking that it fits `WIDTH`.
+    fn fit(&self, value: &[u64]) -> Result<Vec<u64>, ErrorKind> {
+        if word::significant_bits(value) > self.header.width {
+            return Err(ErrorKind::ValueOutOfRange);
+        }
+
+        let mut value = value.to_vec();
+        value.resize(word::limbs(self.header.width), 0);
+
+        Ok(value)
+    }
+}
+
+impl Display for SyntaxTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for node in &self.nodes {
+            f.write_str(&node.leading)?;
+            f.write_str(&node.text)?;
+            f.write_str(&node.trailing)?;
+        }
+
+        f.write_str(&self.tail)
+    }
+}
+
+/// Splits off the statement at the start of `input`, which has already
+/// been validated.
+fn statement<'a>(input: &'a str, header: &MifHeader, content: bool) -> (&'a str, Kind) {
+    if !content {
+        if let Ok((rest, _)) = parser::begin(input) {
+            return (&input[..input.len() - rest.len()], Kind::Begin);
+        }
+
+        let (rest, element) = parser::attribute(input).expect("the header was validated");
+        let text = &input[..input.len() - rest.len()];
+        let header = match element {
+            Element::Width(_) => Header::Width,
+            Element::Depth(_) => Header::Depth,
+            Element::AddressRadix(_) => Header::AddressRadix,
+            Element::DataRadix(_) => Header::DataRadix,
+            _ => unreachable!(),
+        };
+        let equals = text.find('=').map_or(0, |i| i + 1);
+        let value = tokens(text, equals..text.len() - 1, |c| !c.is_whitespace())[0].clone();
+
+        return (text, Kind::Header { header, value });
+    }
+
+    if let Ok((rest, _)) = parser::end(input) {
+        return (&input[..input.len() - rest.len()], Kind::End);
+    }
+
+    let (rest, address) =
+        parser::address(input, header.address_radix).expect("entries were validated");
+    let length = Terminator::default()
+        .find(input.as_bytes())
+        .expect("entries were validated");
+
+    let text = &input[..=length];
+    let span = 0..input.len() - rest.len();
+
+    (
+        text,
+        Kind::Entry {
+            address,
+            values: values(text, &span).len(),
+            span,
+        },
+    )
+}
+
+/// Spans of the values of the data entry `text`, whose address is at
+/// `span`.
+fn values(text: &str, span: &Range<usize>) -> Vec<Range<usize>> {
+    let colon = span.end + text[span.end..].find(':').map_or(0, |i| i + 1);
+
+    tokens(text, colon..text.len() - 1, |c| !c.is_whitespace())
+}
+
+/// Length of what follows a statement on its line: blanks, a `--` comment
+/// and the line break. Nothing is taken if another statement or a block
+/// comment follows on the same line.
+fn line_end(input: &str) -> usize {
+    let rest = input.trim_start_matches([' ', '\t']);
+    let rest = match rest.strip_prefix("--") {
+        Some(comment) => &comment[comment.find(['\n', '\r']).unwrap_or(comment.len())..],
+        None => rest,
+    };
+    let newline = ["\r\n", "\n", "\r"]
+        .into_iter()
+        .find_map(|newline| rest.strip_prefix(newline));
+
+    match newline {
+        Some(rest) => input.len() - rest.len(),
+        None if rest.is_empty() => input.len(),
+        None => 0,
+    }
+}
+
+/// Spans of the runs of characters matching `is_token` in `text[range]`.
+fn tokens(text: &str, range: Range<usize>, is_token: fn(char) -> bool) -> Vec<Range<usize>> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    for (i, c) in text[range.clone()].char_indices() {
+        match (start, is_token(c)) {
+            (None, true) => start = Some(range.start + i),
+            (Some(from), false) => {
+                tokens.push(from..range.start + i);
+                start = None;
+            }
+            _ => (),
+        }
+    }
+
+    if let Some(from) = start {
+        tokens.push(from..range.end);
+    }
+
+    tokens
+}
+
+fn address_word(limbs: &[u64]) -> Word<'_> {
+    Word::new(limbs, 64)
+}
+
+/// Formats `word` in `radix`. With `like`, the token being replaced, the
+/// result takes its zero padding and letter case.
+fn format_word(word: Word, radix: Radix, signed: bool, like: Option<&str>) -> String {
+    let text = match radix {
+        Radix::Bin => format!("{word:b}"),
+        Radix::Oct => format!("{word:o}"),
+        Radix::Hex => format!("{word:X}"),
+        Radix::Dec if signed => word.signed().to_string(),
+        Radix::Dec | Radix::Uns => word.to_string(),
+    };
+    let Some(like) = like else {
+        return text;
+    };
+    let text = match like.chars().any(|c| c.is_ascii_lowercase()) {
+        true => text.to_lowercase(),
+        false => text,
+    };
+
+    match like.starts_with('0') && like.len() > text.len() && !text.starts_with('-') {
+        true => format!("{}{text}", "0".repeat(like.len() - text.len())),
+        false => text,
+    }
+}
+
+/// Whether the value `token` fits in `width` bits.
+fn fits(token: &str, radix: Radix, width: usize) -> bool {
+    parser::value(token, radix, width).is_ok_and(|(_, (_, truncated))| !truncated)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SOURCE: &str = "-- hand written\nWIDTH = 8;\nDEPTH = 16;\nADDRESS_RADIX = HEX;\nDATA_RADIX = HEX;\n\nCONTENT\nBEGIN\n    00 : 0A;  -- first\n    % block %\n    01 : 0B 0C;\n    [04..07] : 11 22;\nEND;\n-- end\n";
+
+    fn edited(edit: impl FnOnce(&mut SyntaxTree)) -> String {
+        let mut tree = SyntaxTree::parse(SOURCE).unwrap();
+        edit(&mut tree);
+        assert_eq!(tree.document(), parser::parse(tree.to_string()).unwrap());
+        tree.to_string()
+    }
+
+    #[test]
+    pub fn lossless() {
+        let tree = SyntaxTree::parse(SOURCE).unwrap();
+
+        assert_eq!(tree.to_string(), SOURCE);
+        assert_eq!(tree.document(), parser::parse(SOURCE).unwrap());
+        assert_eq!(
+            tree.entries()
+                .map(|address| address.from)
+                .collect::<Vec<_>>(),
+            [0, 1, 4]
+        );
+
+        let source =
+            "WIDTH=8;DEPTH=4;ADDRESS_RADIX=UNS;DATA_RADIX=UNS;CONTENT %x% BEGIN 0:1;1:2;END;";
+        assert_eq!(SyntaxTree::parse(source).unwrap().to_string(), source);
+        assert!(SyntaxTree::parse("WIDTH=8;").is_err());
+    }
+
+    #[test]
+    pub fn set_word() {
+        assert_eq!(
+            edited(|tree| tree.set_word(2, &[0xFF]).unwrap()),
+            SOURCE.replace("0B 0C", "0B FF")
+        );
+        assert_eq!(
+            edited(|tree| tree.set_word(0, &[1]).unwrap()),
+            SOURCE.replace("0A", "01")
+        );
+        assert_eq!(
+            edited(|tree| tree.set_word(5, &[0x33]).unwrap()),
+            SOURCE.replace(
+                "    [04..07] : 11 22;\n",
+                "    04 : 11;\n    05 : 33;\n    [06..07] : 11 22;\n"
+            )
+        );
+        assert_eq!(
+            edited(|tree| tree.set_word(6, &[0x33]).unwrap()),
+            SOURCE.replace(
+                "    [04..07] : 11 22;\n",
+                "    [04..05] : 11 22;\n    06 : 33;\n    07 : 22;\n"
+            )
+        );
+        assert_eq!(
+            edited(|tree| tree.set_word(9, &[1]).unwrap()),
+            SOURCE.replace("END;", "    09 : 1;\nEND;")
+        );
+        assert_eq!(edited(|tree| tree.set_word(4, &[0x11]).unwrap()), SOURCE);
+
+        let mut tree = SyntaxTree::parse(SOURCE).unwrap();
+
+        assert_eq!(tree.set_word(16, &[1]), Err(ErrorKind::AddressOutOfRange));
+        assert_eq!(tree.set_word(0, &[0x100]), Err(ErrorKind::ValueOutOfRange));
+
+        tree.set_word(6, &[0x44]).unwrap();
+        assert_eq!(
+            tree.document()
+                .words()
+                .map(|word| word.to_u64().unwrap())
+                .collect::<Vec<_>>()[..8],
+            [0x0A, 0x0B, 0x0C, 0, 0x11, 0x22, 0x44, 0x22]
+        );
+    }
+
+    #[test]
+    pub fn entries() {
+        assert_eq!(
+            edited(|tree| tree
+                .insert_entry(Address { from: 2, to: 3 }, &[&[7]])
+                .unwrap()),
+            SOURCE.replace("    [04", "    [02..03] : 07;\n    [04")
+        );
+        assert_eq!(
+            edited(|tree| assert_eq!(tree.remove_entry(1), Some(Address { from: 1, to: 1 }))),
+            SOURCE.replace("    01 : 0B 0C;\n", "")
+        );
+        assert_eq!(
+            edited(|tree| assert_eq!(tree.remove_entry(0), Some(Address { from: 0, to: 0 }))),
+            SOURCE.replace("    00 : 0A;  -- first\n", "")
+        );
+
+        let mut tree = SyntaxTree::parse(SOURCE).unwrap();
+
+        assert_eq!(tree.remove_entry(3), None);
+        assert_eq!(
+            tree.insert_entry(Address { from: 15, to: 15 }, &[&[1], &[2]]),
+            Err(ErrorKind::AddressOutOfRange)
+        );
+    }
+
+    #[test]
+    pub fn headers() {
+        assert_eq!(
+            edited(|tree| tree.set_depth(8).unwrap()),
+            SOURCE.replace("DEPTH = 16", "DEPTH = 8")
+        );
+        assert_eq!(
+            edited(|tree| tree.set_width(6).unwrap()),
+            SOURCE.replace("WIDTH = 8", "WIDTH = 6")
+        );
+        assert_eq!(
+            edited(|tree| tree.set_address_radix(Radix::Uns)),
+            SOURCE
+                .replace("ADDRESS_RADIX = HEX", "ADDRESS_RADIX = UNS")
+                .replace("00 :", "0 :")
+                .replace("01 :", "1 :")
+                .replace("[04..07]", "[4..7]")
+        );
+        assert_eq!(
+            edited(|tree| tree.set_data_radix(Radix::Uns)),
+            SOURCE
+                .replace("DATA_RADIX = HEX", "DATA_RADIX = UNS")
+                .replace("0A", "10")
+                .replace("0B 0C", "11 12")
+                .replace("11 22", "17 34")
+        );
+
+        let mut tree = SyntaxTree::parse(SOURCE).unwrap();
+
+        assert_eq!(tree.set_depth(7), Err(ErrorKind::AddressOutOfRange));
+        assert_eq!(tree.set_width(4), Err(ErrorKind::ValueOutOfRange));
+        assert_eq!(tree.to_string(), SOURCE);
+    }
+
+    #[test]
+    pub fn default_headers() {
+        let source = "WIDTH=8;\nDEPTH=4;\n-- data\nCONTENT BEGIN\n0: 1;\nEND;\n";
+        let mut tree = SyntaxTree::parse(source).unwrap();
+
+        tree.set_data_radix(Radix::Bin);
+
+        assert_eq!(
+            tree.to_string(),
+            "WIDTH=8;\nDEPTH=4;\nDATA_RADIX=BIN;\n-- data\nCONTENT BEGIN\n0: 1;\nEND;\n"
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use std::fmt::{Binary, Display, LowerHex, Octal, UpperHex};
+
+use crate::writer::ToMif;
+
+/// A single memory word of arbitrary width, stored as little-endian 64-bit
+/// limbs. Bits above `width` are always zero.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Word<'a> {
+    limbs: &'a [u64],
+    width: usize,
+}
+
+impl<'a> Word<'a> {
+    pub(crate) fn new(limbs: &'a [u64], width: usize) -> Self {
+        Self { limbs, width }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The word's limbs, least significant first.
+    pub fn limbs(&self) -> &'a [u64] {
+        self.limbs
+    }
+
+    pub fn bit(&self, index: usize) -> bool {
+        self.limbs
+            .get(index / 64)
+            .is_some_and(|limb| limb >> (index % 64) & 1 == 1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.iter().all(|&limb| limb == 0)
+    }
+
+    /// Returns the word as a `u64`, or `None` if it has bits set above 64.
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.limbs {
+            [] => Some(0),
+            [low, rest @ ..] => rest.iter().all(|&limb| limb == 0).then_some(*low),
+        }
+    }
+
+    /// Returns the word as a `u128`, or `None` if it has bits set above 128.
+    pub fn to_u128(&self) -> Option<u128> {
+        let (low, high) = self.limbs.split_at(self.limbs.len().min(2));
+
+        high.iter().all(|&limb| limb == 0).then(|| {
+            low.iter()
+                .rev()
+                .fold(0u128, |acc, &limb| acc << 64 | limb as u128)
+        })
+    }
+
+    /// Interprets the word as `width`-bit two's complement for formatting.
+    pub fn signed(&self) -> SignedWord<'a> {
+        SignedWord(*self)
+    }
+
+    fn fmt_power_of_two(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        bits: usize,
+        digits: &[u8; 16],
+        prefix: &str,
+    ) -> std::fmt::Result {
+        let used = significant_bits(self.limbs);
+        let text = (0..used.div_ceil(bits).max(1))
+            .rev()
+            .map(|i| digits[extract(self.limbs, i * bits, bits) as usize] as char)
+            .collect::<String>();
+
+        f.pad_integral(true, prefix, &text)
+    }
+}
+
+/// Decimal formatting of a [`Word`] as a signed two's complement value.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SignedWord<'a>(Word<'a>);
+
+impl Display for SignedWord<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let word = self.0;
+
+        if word.width == 0 || !word.bit(word.width - 1) {
+            return f.pad_integral(true, "", &decimal(word.limbs));
+        }
+
+        let mut magnitude = word.limbs.to_vec();
+        negate(&mut magnitude, word.width);
+
+        f.pad_integral(false, "", &decimal(&magnitude))
+    }
+}
+
+impl Display for Word<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad_integral(true, "", &decimal(self.limbs))
+    }
+}
+
+impl Binary for Word<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_power_of_two(f, 1, b"0123456789abcdef", "0b")
+    }
+}
+
+impl Octal for Word<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_power_of_two(f, 3, b"0123456789abcdef", "0o")
+    }
+}
+
+impl LowerHex for Word<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_power_of_two(f, 4, b"0123456789abcdef", "0x")
+    }
+}
+
+impl UpperHex for Word<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_power_of_two(f, 4, b"0123456789ABCDEF", "0x")
+    }
+}
+
+impl<'a> ToMif for Word<'a> {
+    /// Words carry their own width, so writers built from them must set
+    /// `WIDTH` explicitly.
+    const WIDTH: usize = 0;
+
+    type SignedBinary = SignedWord<'a>;
+    type Binary = Word<'a>;
+
+    fn signed(&self) -> Self::SignedBinary {
+        Word::signed(self)
+    }
+
+    fn bits(&self) -> Self::Binary {
+        *self
+    }
+
+    fn limbs(&self) -> Vec<u64> {
+        self.limbs.to_vec()
+    }
+
+    /// Words are written as two's complement under `DEC`.
+    fn is_negative(&self) -> bool {
+        self.width > 0 && self.bit(self.width - 1)
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+}
+
+/// Number of 64-bit limbs needed to hold a `width`-bit word.
+pub(crate) fn limbs(width: usize) -> usize {
+    width.div_ceil(64).max(1)
+}
+
+/// Parses already validated `digits` into `limbs` little-endian limbs,
+/// returning `None` if the value doesn't fit.
+pub(crate) fn from_digits(digits: &str, radix: u32, limbs: usize) -> Option<Vec<u64>> {
+    let mut value = vec![0u64; limbs];
+
+    for c in digits.chars() {
+        let mut carry = c.to_digit(radix)? as u128;
+
+        for limb in value.iter_mut() {
+            let product = *limb as u128 * radix as u128 + carry;
+            *limb = product as u64;
+            carry = product >> 64;
+        }
+
+        if carry != 0 {
+            return None;
+        }
+    }
+
+    Some(value)
+}
+
+/// Clears every bit at or above `width`.
+pub(crate) fn mask(limbs: &mut [u64], width: usize) {
+    for (i, limb) in limbs.iter_mut().enumerate() {
+        let low = i * 64;
+
+        if low >= width {
+            *limb = 0;
+        } else if width - low < 64 {
+            *limb &= (1 << (width - low)) - 1;
+        }
+    }
+}
+
+/// Sets every bit at or above `width`, extending a negative `width`-bit
+/// two's complement value.
+pub(crate) fn sign_extend(limbs: &mut [u64], width: usize) {
+    for (i, limb) in limbs.iter_mut().enumerate() {
+        let low = i * 64;
+
+        if low >= width {
+            *limb = u64::MAX;
+        } else if width - low < 64 {
+            *limb |= u64::MAX << (width - low);
+        }
+    }
+}
+
+/// Replaces `limbs` with its `width`-bit two's complement negation.
+pub(crate) fn negate(limbs: &mut [u64], width: usize) {
+    let mut carry = true;
+
+    for limb in limbs.iter_mut() {
+        (*limb, carry) = (!*limb).overflowing_add(carry as u64);
+    }
+
+    mask(limbs, width);
+}
+
+/// Position of the highest set bit plus one, or 0 for a zero value.
+pub(crate) fn significant_bits(limbs: &[u64]) -> usize {
+    limbs
+        .iter()
+        .rposition(|&limb| limb != 0)
+        .map_or(0, |i| i * 64 + 64 - limbs[i].leading_zeros() as usize)
+}
+
+/// Reads `count` (at most 64) bits starting at bit `offset`.
+pub(crate) fn extract(limbs: &[u64], offset: usize, count: usize) -> u64 {
+    let index = offset / 64;
+    let shift = offset % 64;
+    let low = limbs.get(index).map_or(0, |limb| limb >> shift);
+    let high = match shift {
+        0 => 0,
+        _ => limbs.get(index + 1).map_or(0, |limb| limb << (64 - shift)),
+    };
+
+    (low | high) & u64::MAX.checked_shr(64 - count as u32).unwrap_or(0)
+}
+
+fn decimal(limbs: &[u64]) -> String {
+    const BASE: u128 = 10_000_000_000_000_000_000;
+
+    let mut value = limbs.to_vec();
+    let mut parts = Vec::new();
+
+    loop {
+        let mut remainder = 0u128;
+
+        for limb in value.iter_mut().rev() {
+            let current = remainder << 64 | *limb as u128;
+            *limb = (current / BASE) as u64;
+            remainder = current % BASE;
+        }
+
+        parts.push(remainder as u64);
+
+        if value.iter().all(|&limb| limb == 0) {
+            break;
+        }
+    }
+
+    let mut text = parts.pop().unwrap_or(0).to_string();
+
+    for part in parts.iter().rev() {
+        text.push_str(&format!("{part:019}"));
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn formatting() {
+        let limbs = [0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210, 0x1];
+        let word = Word::new(&limbs, 129);
+        let expected = 0xFEDC_BA98_7654_3210_0123_4567_89AB_CDEFu128;
+
+        assert_eq!(format!("{word:X}"), format!("1{:032X}", expected));
+        assert_eq!(format!("{word:x}"), format!("1{:032x}", expected));
+        assert_eq!(format!("{word:b}"), format!("1{:0128b}", expected));
+        assert_eq!(format!("{word}"), "679052367766672755979416859493853285871");
+        assert_eq!(format!("{:06X}", Word::new(&[0xAB], 24)), "0000AB");
+        assert_eq!(format!("{:o}", Word::new(&[0], 8)), "0");
+    }
+
+    #[test]
+    pub fn signed() {
+        assert_eq!(Word::new(&[0xFF], 8).signed().to_string(), "-1");
+        assert_eq!(Word::new(&[0x80], 8).signed().to_string(), "-128");
+        assert_eq!(Word::new(&[0x7F], 8).signed().to_string(), "127");
+        assert_eq!(
+            Word::new(&[1, 2], 66).signed().to_string(),
+            "-36893488147419103231"
+        );
+    }
+
+    #[test]
+    pub fn digits() {
+        assert_eq!(from_digits("FFFF", 16, 1), Some(vec![0xFFFF]));
+        assert_eq!(from_digits("10000000000000000", 16, 2), Some(vec![0, 1]));
+        assert_eq!(from_digits("10000000000000000", 16, 1), None);
+    }
+}
@@ -1,140 +1,756 @@
-use std::fmt::{Binary, Display, Octal, UpperHex};
+use std::{
+    borrow::{Borrow, Cow},
+    collections::HashMap,
+    fmt::{Binary, Display, Octal, UpperHex},
+    ops::Range,
+};
 
-use crate::Radix;
+use wasm_bindgen::prelude::*;
 
+use crate::{
+    encoder,
+    error::ErrorKind,
+    parser::Address,
+    word::{self, Word},
+    Radix,
+};
+
+/// How [`Mif`] groups addresses into entries.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Encoding {
+    /// Each run of equal values is one `[from..to]` range.
+    #[default]
+    Runs = 0,
+    /// The shortest text found using runs, ranges repeating a pattern of up
+    /// to 16 values and entries of consecutive values, no two of them
+    /// assigning the same address. Addresses without a value may be written
+    /// with the value they read as, zero without a fill.
+    ///
+    /// Runs of 64 or more addresses without a value of their own split the
+    /// rest into parts planned on their own, so the work is linear in the
+    /// number of values written and the short gaps between them. Such a run
+    /// is written as one range if there is a fill value.
+    Smallest = 1,
+    /// Like [`Encoding::Smallest`], also trying a range of the most common
+    /// value, or of the fill value over all of `DEPTH`, written first and
+    /// overridden by later entries.
+    ///
+    /// This relies on later entries winning, as parsing with the default
+    /// [`crate::Overlap`] does, and the parser warns of each override.
+    SmallestOverriding = 2,
+}
+
+/// How [`Mif`] lays out its text.
+///
+/// The default pads addresses and data with zeros, writes hex in uppercase
+/// and one word per entry, with runs of equal values as ranges, no spaces or
+/// indentation and `\n` line endings.
+#[wasm_bindgen]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FormatOptions {
+    /// Zero pad `BIN`, `OCT` and `HEX` addresses to the digits of the
+    /// highest one, `DEPTH - 1`.
+    pub pad_addresses: bool,
+    /// Zero pad `BIN`, `OCT` and `HEX` values to the digits of `WIDTH`.
+    pub pad_data: bool,
+    /// Write hex digits in uppercase.
+    pub uppercase: bool,
+    /// Put a space on each side of an entry's `:`.
+    pub spaced: bool,
+    /// Written before each entry.
+    #[wasm_bindgen(skip)]
+    pub indent: String,
+    /// End lines with `\r\n` instead of `\n`.
+    pub crlf: bool,
+    /// Write runs of equal values as ranges, or else every address on its
+    /// own. Only [`Encoding::Runs`] uses this.
+    pub grouping: bool,
+    /// See [`Mif::with_words_per_line`].
+    pub words_per_line: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            pad_addresses: true,
+            pad_data: true,
+            uppercase: true,
+            spaced: false,
+            indent: String::new(),
+            crlf: false,
+            grouping: true,
+            words_per_line: 1,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl FormatOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Laid out like the files Quartus writes: entries indented with a tab
+    /// and spaces around their `:`.
+    pub fn quartus() -> Self {
+        Self {
+            spaced: true,
+            indent: "\t".to_owned(),
+            ..Self::default()
+        }
+    }
+
+    /// As little text as the entries take: no padding, spaces or
+    /// indentation.
+    pub fn compact() -> Self {
+        Self {
+            pad_addresses: false,
+            pad_data: false,
+            ..Self::default()
+        }
+    }
+
+    /// Every address on a line of its own, so a changed word changes only
+    /// its line in a diff.
+    #[wasm_bindgen(js_name = "oneWordPerLine")]
+    pub fn one_word_per_line() -> Self {
+        Self {
+            grouping: false,
+            ..Self::default()
+        }
+    }
+
+    #[wasm_bindgen(getter, js_name = "indent")]
+    pub fn get_indent(&self) -> String {
+        self.indent.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_indent(&mut self, indent: String) {
+        self.indent = indent;
+    }
+}
+
+impl FormatOptions {
+    fn newline(&self) -> &'static str {
+        match self.crlf {
+            true => "\r\n",
+            false => "\n",
+        }
+    }
+
+    fn colon(&self) -> &'static str {
+        match self.spaced {
+            true => " : ",
+            false => ":",
+        }
+    }
+}
+
+/// Writes values as a MIF file through its [`Display`] implementation.
+///
+/// `WIDTH` is the bit size of `T` and `DEPTH` one past the highest address
+/// written unless set with [`Mif::with_width`] and [`Mif::with_depth`].
+/// Entries are grouped as [`Encoding::Runs`] and laid out as the default
+/// [`FormatOptions`] unless set with [`Mif::with_encoding`] and
+/// [`Mif::with_format`].
 pub struct Mif<'a, T> {
     address_radix: Radix,
     data_radix: Radix,
-    data: &'a [T],
+    width: usize,
+    depth: Option<usize>,
+    encoding: Encoding,
+    format: FormatOptions,
+    /// Set by [`Mif::with_words_per_line`], over the format's own.
+    words_per_line: Option<usize>,
+    /// Set by [`Mif::try_with_width`], so values must fit in `WIDTH`.
+    strict: bool,
+    fill: Option<Value>,
+    values: Values<'a, T>,
 }
 
-impl<'a, T: ToMif> Mif<'a, T> {
-    const BIN_WIDTH: usize = T::WIDTH;
-    const HEX_WIDTH: usize = T::WIDTH / 4;
-    const OCT_WIDTH: usize = T::WIDTH / 3 + 1;
+/// The values a [`Mif`] writes at addresses of their own.
+enum Values<'a, T> {
+    /// `data[i]` at address `i`, borrowed as given.
+    Dense(&'a [T]),
+    /// Sorted by address, each address at most once.
+    Sparse(Vec<(usize, Value)>),
+}
 
+impl<'a, T: ToMif> Mif<'a, T> {
+    /// Writes `data` from address 0, with every address past it up to
+    /// `DEPTH` set to zero unless [`Mif::with_fill`] says otherwise.
     pub fn new(data: &'a [T], address_radix: Radix, data_radix: Radix) -> Self {
+        let mut mif = Self::with_values(Values::Dense(data), address_radix, data_radix);
+
+        mif.fill = Some(Value {
+            limbs: vec![0],
+            negative: false,
+            width: 0,
+        });
+        mif
+    }
+
+    /// Writes each segment's values from its base address on. Where
+    /// segments overlap, the later one wins.
+    ///
+    /// Only the addresses in a segment are written, other addresses are
+    /// left to the reader's default unless a fill value is set.
+    pub fn from_segments(
+        segments: &[(usize, &'a [T])],
+        address_radix: Radix,
+        data_radix: Radix,
+    ) -> Self {
+        Self::from_pairs(
+            segments.iter().flat_map(|(base, data)| {
+                data.iter()
+                    .enumerate()
+                    .map(move |(i, value)| (base + i, value))
+            }),
+            address_radix,
+            data_radix,
+        )
+    }
+
+    /// Writes each value at its address, in any order. Where an address is
+    /// given more than once, the last value wins.
+    ///
+    /// Only the addresses given are written, other addresses are left to the
+    /// reader's default unless a fill value is set.
+    pub fn from_pairs<V: Borrow<T>>(
+        pairs: impl IntoIterator<Item = (usize, V)>,
+        address_radix: Radix,
+        data_radix: Radix,
+    ) -> Self {
+        let mut values = pairs
+            .into_iter()
+            .map(|(address, value)| (address, Value::new(value.borrow())))
+            .collect::<Vec<_>>();
+
+        // The sort is stable, so of equal addresses the last one given is
+        // kept.
+        values.sort_by_key(|(address, _)| *address);
+        values.reverse();
+        values.dedup_by_key(|(address, _)| *address);
+        values.reverse();
+
+        Self::with_values(Values::Sparse(values), address_radix, data_radix)
+    }
+
+    fn with_values(values: Values<'a, T>, address_radix: Radix, data_radix: Radix) -> Self {
         Self {
-            data,
             address_radix,
             data_radix,
+            width: T::WIDTH,
+            depth: None,
+            encoding: Encoding::default(),
+            format: FormatOptions::default(),
+            words_per_line: None,
+            strict: false,
+            fill: None,
+            values,
         }
     }
-}
 
-macro_rules! write_data {
-    ($f:expr, $data:expr, $index:expr, $value:expr, signed) => {{
-        let Some(first) = $data.next() else {
-            return Ok(());
+    /// Sets `WIDTH`. Values are truncated to their low `width` bits, or sign
+    /// extended if they are negative and narrower.
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self.strict = false;
+        self
+    }
+
+    /// Like [`Mif::with_width`], but fails with
+    /// [`ErrorKind::ValueOutOfRange`] if a value doesn't fit in `width` bits,
+    /// as an unsigned number or, if it is negative, as two's complement.
+    /// A fill value set later must fit as well.
+    pub fn try_with_width(self, width: usize) -> Result<Self, ErrorKind> {
+        let fits = self.values().all(|(_, value)| value.fits(width))
+            && self.fill.iter().all(|value| value.fits(width));
+
+        match fits {
+            true => Ok(Self {
+                strict: true,
+                ..self.with_width(width)
+            }),
+            false => Err(ErrorKind::ValueOutOfRange),
+        }
+    }
+
+    /// Sets `DEPTH`. Fails with [`ErrorKind::AddressOutOfRange`] if a value
+    /// is at `depth` or above.
+    pub fn with_depth(mut self, depth: usize) -> Result<Self, ErrorKind> {
+        if self.end() > depth {
+            return Err(ErrorKind::AddressOutOfRange);
+        }
+
+        self.depth = Some(depth);
+        Ok(self)
+    }
+
+    /// Sets the value written to every address below `DEPTH` that has no
+    /// value of its own. Fails with [`ErrorKind::ValueOutOfRange`] if it
+    /// doesn't fit in a `WIDTH` set with [`Mif::try_with_width`].
+    pub fn with_fill(mut self, value: T) -> Result<Self, ErrorKind> {
+        let value = Value::new(&value);
+
+        if self.strict && !value.fits(self.width) {
+            return Err(ErrorKind::ValueOutOfRange);
+        }
+
+        self.fill = Some(value);
+        Ok(self)
+    }
+
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Writes up to `words` consecutive values per entry, with each line
+    /// starting at a multiple of `words` like a hex dump, unless addresses
+    /// before it on that line have no value. Runs of equal values still
+    /// become ranges for the whole lines they cover.
+    ///
+    /// Only [`Encoding::Runs`] uses this, [`Encoding::Smallest`] picks its
+    /// own lines. Always overrides [`FormatOptions::words_per_line`], even
+    /// when [`Mif::with_format`] is called afterwards.
+    pub fn with_words_per_line(mut self, words: usize) -> Self {
+        self.words_per_line = Some(words);
+        self
+    }
+
+    /// Sets the layout, except for a count of words per line set with
+    /// [`Mif::with_words_per_line`].
+    pub fn with_format(mut self, format: FormatOptions) -> Self {
+        self.format = format;
+        self
+    }
+
+    fn depth(&self) -> usize {
+        self.depth.unwrap_or_else(|| self.end())
+    }
+
+    /// One past the highest address with a value of its own.
+    fn end(&self) -> usize {
+        match &self.values {
+            Values::Dense(data) => data.len(),
+            Values::Sparse(values) => values.last().map_or(0, |(address, _)| address + 1),
+        }
+    }
+
+    /// Every address with a value of its own and that value, in ascending
+    /// order.
+    fn values(&self) -> impl Iterator<Item = (usize, Cow<'_, Value>)> + '_ {
+        let (dense, sparse) = match &self.values {
+            Values::Dense(data) => (*data, &[][..]),
+            Values::Sparse(values) => (&[][..], &values[..]),
         };
 
-        let group = $data.try_fold(
-            GroupState {
-                start: 0,
-                length: 1,
-                value: first.1.signed(),
-            },
-            |mut acc, (index, value)| {
-                let value = value.signed();
+        dense
+            .iter()
+            .enumerate()
+            .map(|(address, value)| (address, Cow::Owned(Value::new(value))))
+            .chain(
+                sparse
+                    .iter()
+                    .map(|(address, value)| (*address, Cow::Borrowed(value))),
+            )
+    }
 
-                if acc.value == value {
-                    acc.length += 1;
-                } else {
-                    if index - acc.start > 1 {
-                        write!($f, "[");
-                        write!($f, $index, acc.start);
-                        write!($f, "..");
-                        write!($f, $index, index - 1);
-                        write!($f, "]:");
-                    } else {
-                        write!($f, $index, acc.start);
-                        write!($f, ":");
-                    }
+    /// Every run of consecutive addresses holding the same value, with the
+    /// gaps between them filled if there is a fill value.
+    fn runs(&self) -> Vec<(Range<usize>, Value)> {
+        let mut runs = Vec::new();
+        let mut next = 0;
+
+        for (address, value) in self.values() {
+            self.push_run(&mut runs, next..address, self.fill.as_ref());
+            self.push_run(&mut runs, address..address + 1, Some(&value));
+            next = address + 1;
+        }
+
+        self.push_run(&mut runs, next..self.depth(), self.fill.as_ref());
+
+        runs
+    }
+
+    /// Adds `value` at `addresses` to `runs`, merged with the last run if
+    /// that holds the same value right before.
+    fn push_run(
+        &self,
+        runs: &mut Vec<(Range<usize>, Value)>,
+        addresses: Range<usize>,
+        value: Option<&Value>,
+    ) {
+        let Some(value) = value.filter(|_| !addresses.is_empty()) else {
+            return;
+        };
+        let value = self.truncate(value);
+
+        match runs.last_mut() {
+            Some((run, last)) if run.end == addresses.start && *last == value => {
+                run.end = addresses.end;
+            }
+            _ => runs.push((addresses, value)),
+        }
+    }
+
+    /// `value` as written, truncated or sign extended to `WIDTH`.
+    fn truncate(&self, value: &Value) -> Value {
+        let mut limbs = value.limbs.clone();
+
+        limbs.resize(word::limbs(self.width), 0);
+
+        if value.negative {
+            word::sign_extend(&mut limbs, value.width);
+        }
 
-                    write!($f, $value, acc.value);
-                    write!($f, ";\n");
+        word::mask(&mut limbs, self.width);
+
+        Value {
+            limbs,
+            negative: value.negative,
+            width: self.width,
+        }
+    }
+
+    /// The entries to write, each assigning its values like a parsed
+    /// [`crate::parser::Record`].
+    fn entries(&self) -> Vec<(Address, Vec<Value>)> {
+        match self.encoding {
+            Encoding::Runs => self.lines(),
+            Encoding::Smallest => self.smallest(false),
+            Encoding::SmallestOverriding => self.smallest(true),
+        }
+    }
 
-                    acc.start = index;
-                    acc.value = value;
-                    acc.length = 1;
+    /// Every run as a range over the whole lines it covers, unless grouping
+    /// is off, and the rest as lines of consecutive values.
+    fn lines(&self) -> Vec<(Address, Vec<Value>)> {
+        let words = self
+            .words_per_line
+            .unwrap_or(self.format.words_per_line)
+            .max(1);
+        let mut entries = Vec::new();
+
+        for (addresses, value) in self.runs() {
+            let lines = addresses.start.next_multiple_of(words)..addresses.end / words * words;
+
+            if lines.is_empty() || !self.format.grouping {
+                addresses.for_each(|address| push_word(&mut entries, words, address, &value));
+                continue;
+            }
+
+            for address in addresses.start..lines.start {
+                push_word(&mut entries, words, address, &value);
+            }
+
+            entries.push((
+                Address {
+                    from: lines.start,
+                    to: lines.end - 1,
+                },
+                vec![value.clone()],
+            ));
+
+            for address in lines.end..addresses.end {
+                push_word(&mut entries, words, address, &value);
+            }
+        }
+
+        entries
+    }
+
+    /// The entries [`Encoding::Smallest`] writes, or with `overrides`
+    /// [`Encoding::SmallestOverriding`].
+    fn smallest(&self, overrides: bool) -> Vec<(Address, Vec<Value>)> {
+        let zero = Value {
+            limbs: vec![0; word::limbs(self.width)],
+            negative: false,
+            width: self.width,
+        };
+        let mut values = vec![zero.clone()];
+        let mut ids = HashMap::from([(zero, 0)]);
+        let mut id = |value: &Value| {
+            let value = self.truncate(value);
+
+            *ids.entry(value.clone()).or_insert_with(|| {
+                values.push(value);
+                values.len() - 1
+            })
+        };
+
+        let fill = self.fill.as_ref().map(&mut id);
+        // Values equal to the fill are written as the fill is.
+        let words = self
+            .values()
+            .map(|(address, value)| (address, id(&value)))
+            .filter(|&(_, id)| Some(id) != fill)
+            .collect::<Vec<_>>();
+
+        let lengths = values
+            .iter()
+            .map(|value| text_length(self.data(value)))
+            .collect::<Vec<_>>();
+        let padding = self.address_padding();
+        let depth = self.depth();
+        // Addresses only get longer, so where each length starts is found
+        // once instead of writing every address.
+        let mut starts = vec![(0, text_length(self.address(0, padding)))];
+
+        while let Some(&(start, length)) = starts.last() {
+            let longer = |address| text_length(self.address(address, padding)) > length;
+            let (mut low, mut high) = (start, depth);
+
+            while low < high {
+                let middle = low + (high - low) / 2;
+
+                match longer(middle) {
+                    true => high = middle,
+                    false => low = middle + 1,
                 }
-                Ok(acc)
+            }
+
+            match low < depth {
+                true => starts.push((low, text_length(self.address(low, padding)))),
+                false => break,
+            }
+        }
+
+        let costs = encoder::Costs {
+            values: &lengths,
+            address: &|address| {
+                starts[starts.partition_point(|&(start, _)| start <= address) - 1].1
             },
-        )?;
-
-        if group.length > 1 {
-            write!($f, "[");
-            write!($f, $index, group.start);
-            write!($f, "..");
-            write!($f, $index, group.start + group.length - 1);
-            write!($f, "]:");
-        } else {
-            write!($f, $index, group.start);
-            write!($f, ":");
-        }
-
-        write!($f, $value, group.value);
-        write!($f, ";\n");
-    }};
-    ($f:expr, $data:expr, $index:expr, $value:expr) => {{
-        let Some(first) = $data.next() else {
-            return Ok(());
+            entry: self.format.indent.len()
+                + self.format.colon().len()
+                + ";".len()
+                + self.format.newline().len(),
+            range: "[..]".len(),
+            separator: " ".len(),
         };
 
-        let group = $data.try_fold(
-            GroupState {
-                start: 0,
-                length: 1,
-                value: first.1.bits(),
-            },
-            |mut acc, (index, value)| {
-                let value = value.bits();
+        // Plans the parts between gaps of at least `encoder::GAP` addresses
+        // on their own, with `gaps` the value of addresses without one and a
+        // long gap of a value written as one range.
+        let plan = |gaps: Option<usize>, zero: usize| {
+            let mut entries = Vec::new();
+            let mut part = Vec::new();
+            let mut start = 0;
+            let mut next = 0;
+
+            for (address, id) in words
+                .iter()
+                .map(|&(address, id)| (address, Some(id)))
+                .chain([(depth, None)])
+            {
+                let gap = address - next;
 
-                if acc.value == value {
-                    acc.length += 1;
+                if gap < encoder::GAP {
+                    part.extend(std::iter::repeat_n(gaps, gap));
                 } else {
-                    if index - acc.start > 1 {
-                        write!($f, "[");
-                        write!($f, $index, acc.start);
-                        write!($f, "..");
-                        write!($f, $index, index - 1);
-                        write!($f, "]:");
-                    } else {
-                        write!($f, $index, acc.start);
-                        write!($f, ":");
+                    entries.extend(encoder::smallest(&part, start, zero, &costs, overrides));
+                    part.clear();
+                    start = address;
+
+                    if let Some(value) = gaps {
+                        entries.push((
+                            Address {
+                                from: next,
+                                to: address - 1,
+                            },
+                            vec![value],
+                        ));
                     }
+                }
 
-                    write!($f, $value, acc.value);
-                    write!($f, ";\n");
+                part.extend(id.map(Some));
+                next = address + 1;
+            }
 
-                    acc.start = index;
-                    acc.value = value;
-                    acc.length = 1;
-                }
-                Ok(acc)
+            entries.extend(encoder::smallest(&part, start, zero, &costs, overrides));
+            entries
+        };
+
+        let mut entries = plan(fill, 0);
+
+        // One range of the fill under everything else, which leaves only
+        // the other values to write.
+        if let (true, Some(fill), 1..) = (overrides, fill, depth) {
+            let mut layered = vec![(
+                Address {
+                    from: 0,
+                    to: depth - 1,
+                },
+                vec![fill],
+            )];
+
+            layered.extend(plan(None, fill));
+
+            if costs.of(&layered) < costs.of(&entries) {
+                entries = layered;
+            }
+        }
+
+        entries
+            .into_iter()
+            .map(|(address, entry)| {
+                (
+                    address,
+                    entry.into_iter().map(|id| values[id].clone()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// Digits of the highest address, if addresses are padded.
+    fn address_padding(&self) -> usize {
+        let highest = self.depth().saturating_sub(1);
+
+        match self.format.pad_addresses {
+            true => padding(
+                self.address_radix,
+                highest.checked_ilog2().map_or(0, |log| log as usize + 1),
+            ),
+            false => 0,
+        }
+    }
+
+    fn address(&self, address: usize, padding: usize) -> Number<'static> {
+        Number {
+            limbs: Cow::Owned(vec![address as u64]),
+            width: usize::BITS as usize,
+            radix: self.address_radix,
+            padding,
+            uppercase: self.format.uppercase,
+            negative: false,
+        }
+    }
+
+    fn data<'v>(&self, value: &'v Value) -> Number<'v> {
+        Number {
+            limbs: Cow::Borrowed(&value.limbs),
+            width: self.width,
+            radix: self.data_radix,
+            padding: match self.format.pad_data {
+                true => padding(self.data_radix, self.width),
+                false => 0,
             },
-        )?;
-
-        if group.length > 1 {
-            write!($f, "[");
-            write!($f, $index, group.start);
-            write!($f, "..");
-            write!($f, $index, group.start + group.length - 1);
-            write!($f, "]:");
-        } else {
-            write!($f, $index, group.start);
-            write!($f, ":");
-        }
-
-        write!($f, $value, group.value);
-        write!($f, ";\n");
-    }};
+            uppercase: self.format.uppercase,
+            negative: value.negative,
+        }
+    }
 }
 
-struct GroupState<T: PartialEq> {
-    start: usize,
-    length: usize,
-    value: T,
+/// Adds `value` at `address` to the last entry if that is a line of
+/// consecutive values it continues, or starts a new line.
+fn push_word(
+    entries: &mut Vec<(Address, Vec<Value>)>,
+    words: usize,
+    address: usize,
+    value: &Value,
+) {
+    match entries.last_mut() {
+        Some((line, values))
+            if line.from == line.to
+                && line.from + values.len() == address
+                && !address.is_multiple_of(words) =>
+        {
+            values.push(value.clone());
+        }
+        _ => entries.push((
+            Address {
+                from: address,
+                to: address,
+            },
+            vec![value.clone()],
+        )),
+    }
+}
+
+/// A value to write, as limbs, remembering whether it was negative so `DEC`
+/// only writes a sign for signed values.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct Value {
+    limbs: Vec<u64>,
+    negative: bool,
+    width: usize,
+}
+
+impl Value {
+    fn new<T: ToMif>(value: &T) -> Self {
+        Self {
+            limbs: value.limbs(),
+            negative: value.is_negative(),
+            width: value.width(),
+        }
+    }
+
+    /// Whether the value fits in `width` bits, as an unsigned number or, if
+    /// it is negative, as two's complement.
+    fn fits(&self, width: usize) -> bool {
+        let word = Word::new(&self.limbs, self.width);
+
+        word::significant_bits(&self.limbs) <= width
+            || self.negative && (width.max(1) - 1..self.width).all(|bit| word.bit(bit))
+    }
+}
+
+/// Digits needed to write any `width`-bit value in `radix`, for zero
+/// padding. Decimal values aren't padded.
+fn padding(radix: Radix, width: usize) -> usize {
+    match radix {
+        Radix::Bin => width,
+        Radix::Oct => width.div_ceil(3),
+        Radix::Hex => width.div_ceil(4),
+        Radix::Dec | Radix::Uns => 0,
+    }
+}
+
+/// Length of `text` once written, without building it.
+fn text_length(text: impl Display) -> usize {
+    struct Counter(usize);
+
+    impl std::fmt::Write for Counter {
+        fn write_str(&mut self, s: &str) -> std::fmt::Result {
+            self.0 += s.len();
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    std::fmt::Write::write_fmt(&mut counter, format_args!("{text}")).expect("counting never fails");
+    counter.0
+}
+
+/// A word written in `radix`, zero padded to `padding` digits. Under `DEC`
+/// a `negative` word is written as two's complement.
+struct Number<'a> {
+    limbs: Cow<'a, [u64]>,
+    width: usize,
+    radix: Radix,
+    padding: usize,
+    uppercase: bool,
+    negative: bool,
+}
+
+impl Display for Number<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let word = Word::new(&self.limbs, self.width);
+        let padding = self.padding;
+
+        match self.radix {
+            Radix::Bin => write!(f, "{word:0padding$b}"),
+            Radix::Oct => write!(f, "{word:0padding$o}"),
+            Radix::Hex if self.uppercase => write!(f, "{word:0padding$X}"),
+            Radix::Hex => write!(f, "{word:0padding$x}"),
+            Radix::Dec if self.negative => write!(f, "{}", word.signed()),
+            Radix::Dec | Radix::Uns => write!(f, "{word}"),
+        }
+    }
 }
 
 impl<'a, T> Display for Mif<'a, T>
@@ -142,73 +758,41 @@ where
     T: ToMif,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let depth = self.data.len();
-        let width = T::WIDTH;
-        let bin_width = Self::BIN_WIDTH;
-        let oct_width = Self::OCT_WIDTH;
-        let hex_width = Self::HEX_WIDTH;
+        let depth = self.depth();
+        let width = self.width;
         let address_radix = self.address_radix;
         let data_radix = self.data_radix;
-        let mut data = self.data.iter().enumerate();
+        let format = &self.format;
+        let newline = format.newline();
+        let padding = self.address_padding();
 
-        f.write_fmt(format_args!("DEPTH={depth};\nWIDTH={width};\nADDRESS_RADIX={address_radix};\nDATA_RADIX={data_radix};\nCONTENT BEGIN\n"))?;
+        f.write_fmt(format_args!("DEPTH={depth};{newline}WIDTH={width};{newline}ADDRESS_RADIX={address_radix};{newline}DATA_RADIX={data_radix};{newline}CONTENT BEGIN{newline}"))?;
 
-        match (address_radix, data_radix) {
-            (Radix::Bin, Radix::Bin) => {
-                write_data!(f, data, "{:0bin_width$b}", "{:0bin_width$b}")
-            }
-            (Radix::Bin, Radix::Dec) => {
-                write_data!(f, data, "{:0bin_width$b}", "{}", signed)
-            }
-            (Radix::Bin, Radix::Hex) => {
-                write_data!(f, data, "{:0bin_width$b}", "{:0hex_width$X}")
-            }
-            (Radix::Bin, Radix::Oct) => {
-                write_data!(f, data, "{:0bin_width$b}", "{:0oct_width$o}")
-            }
-            (Radix::Bin, Radix::Uns) => write_data!(f, data, "{:0bin_width$b}", "{}"),
+        for (Address { from, to }, values) in self.entries() {
+            f.write_str(&format.indent)?;
 
-            (Radix::Hex, Radix::Bin) => {
-                write_data!(f, data, "{:0hex_width$X}", "{:0bin_width$b}")
+            match from == to {
+                true => write!(f, "{}", self.address(from, padding))?,
+                false => write!(
+                    f,
+                    "[{}..{}]",
+                    self.address(from, padding),
+                    self.address(to, padding)
+                )?,
             }
-            (Radix::Hex, Radix::Dec) => {
-                write_data!(f, data, "{:0hex_width$X}", "{}", signed)
-            }
-            (Radix::Hex, Radix::Hex) => {
-                write_data!(f, data, "{:0hex_width$X}", "{:0hex_width$X}")
-            }
-            (Radix::Hex, Radix::Oct) => {
-                write_data!(f, data, "{:0hex_width$X}", "{:0oct_width$o}")
-            }
-            (Radix::Hex, Radix::Uns) => write_data!(f, data, "{:0hex_width$X}", "{}"),
 
-            (Radix::Oct, Radix::Bin) => {
-                write_data!(f, data, "{:0oct_width$o}", "{:0bin_width$b}")
-            }
-            (Radix::Oct, Radix::Dec) => {
-                write_data!(f, data, "{:0oct_width$o}", "{}", signed)
-            }
-            (Radix::Oct, Radix::Hex) => {
-                write_data!(f, data, "{:0oct_width$o}", "{:0hex_width$X}")
-            }
-            (Radix::Oct, Radix::Oct) => {
-                write_data!(f, data, "{:0oct_width$o}", "{:0oct_width$o}")
-            }
-            (Radix::Oct, Radix::Uns) => write_data!(f, data, "{:0oct_width$o}", "{}"),
+            f.write_str(format.colon())?;
 
-            (Radix::Dec | Radix::Uns, Radix::Bin) => {
-                write_data!(f, data, "{}", "{:0bin_width$b}")
-            }
-            (Radix::Dec | Radix::Uns, Radix::Dec) => {
-                write_data!(f, data, "{}", "{}", signed)
-            }
-            (Radix::Dec | Radix::Uns, Radix::Hex) => {
-                write_data!(f, data, "{}", "{:0hex_width$X}")
-            }
-            (Radix::Dec | Radix::Uns, Radix::Oct) => {
-                write_data!(f, data, "{}", "{:0oct_width$o}")
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(" ")?;
+                }
+
+                write!(f, "{}", self.data(value))?;
             }
-            (Radix::Dec | Radix::Uns, Radix::Uns) => write_data!(f, data, "{}", "{}"),
+
+            f.write_str(";")?;
+            f.write_str(newline)?;
         }
 
         f.write_str("END;")
@@ -223,6 +807,26 @@ pub trait ToMif {
 
     fn signed(&self) -> Self::SignedBinary;
     fn bits(&self) -> Self::Binary;
+
+    /// The value's `WIDTH` bits as little-endian 64-bit limbs. The default
+    /// reads them back from [`ToMif::bits`] written in hex, and panics if
+    /// they don't fit in `WIDTH`.
+    fn limbs(&self) -> Vec<u64> {
+        word::from_digits(&format!("{:X}", self.bits()), 16, word::limbs(Self::WIDTH))
+            .filter(|limbs| word::significant_bits(limbs) <= Self::WIDTH)
+            .expect("ToMif::bits should fit in WIDTH")
+    }
+
+    /// Whether [`ToMif::signed`] is below zero, so `DEC` writes it with a
+    /// sign.
+    fn is_negative(&self) -> bool {
+        self.signed().to_string().starts_with('-')
+    }
+
+    /// The value's bit size, `WIDTH` unless it carries its own.
+    fn width(&self) -> usize {
+        Self::WIDTH
+    }
 }
 
 macro_rules! impl_to_mif {
@@ -242,6 +846,10 @@ macro_rules! impl_to_mif {
             fn bits(&self) -> Self::Binary {
                 self.to_bits()
             }
+
+            fn limbs(&self) -> Vec<u64> {
+                limbs(self.to_bits() as u128, Self::WIDTH)
+            }
         }
     };
     ($s:ty, $u:ty) => {
@@ -258,6 +866,14 @@ macro_rules! impl_to_mif {
             fn bits(&self) -> Self::Binary {
                 self.cast_unsigned()
             }
+
+            fn limbs(&self) -> Vec<u64> {
+                limbs(self.cast_unsigned() as u128, Self::WIDTH)
+            }
+
+            fn is_negative(&self) -> bool {
+                *self < 0
+            }
         }
 
         impl ToMif for $u {
@@ -273,6 +889,10 @@ macro_rules! impl_to_mif {
             fn bits(&self) -> Self::Binary {
                 *self
             }
+
+            fn limbs(&self) -> Vec<u64> {
+                limbs(*self as u128, Self::WIDTH)
+            }
         }
 
         impl ToMif for std::num::NonZero<$s> {
@@ -288,6 +908,14 @@ macro_rules! impl_to_mif {
             fn bits(&self) -> Self::Binary {
                 self.get().cast_unsigned()
             }
+
+            fn limbs(&self) -> Vec<u64> {
+                self.get().limbs()
+            }
+
+            fn is_negative(&self) -> bool {
+                self.get() < 0
+            }
         }
 
         impl ToMif for std::num::NonZero<$u> {
@@ -303,10 +931,19 @@ macro_rules! impl_to_mif {
             fn bits(&self) -> Self::Binary {
                 self.get()
             }
+
+            fn limbs(&self) -> Vec<u64> {
+                self.get().limbs()
+            }
         }
     };
 }
 
+/// The low `width` bits of `value` as limbs.
+fn limbs(value: u128, width: usize) -> Vec<u64> {
+    [value as u64, (value >> 64) as u64][..word::limbs(width)].to_vec()
+}
+
 impl_to_mif!(i8, u8);
 #[cfg(feature = "f16")]
 impl_to_mif!(i16, u16, f16);
@@ -327,17 +964,118 @@ mod wasm {
 
     use crate::Radix;
 
+    use super::{Encoding, FormatOptions, ToMif};
+
     #[wasm_bindgen]
     struct Mif;
 
+    /// The settings the encoders take, each left as [`super::Mif`] has it
+    /// when unset.
+    #[wasm_bindgen]
+    #[derive(Clone, Default)]
+    pub struct EncodeOptions {
+        pub width: Option<usize>,
+        pub depth: Option<usize>,
+        /// The fill's bits, truncated to the type written, so a negative
+        /// fill is given in two's complement and `2n ** 64n - 1n` fills with
+        /// ones whatever the type.
+        pub fill: Option<u64>,
+        pub encoding: Encoding,
+        #[wasm_bindgen(skip)]
+        pub format: FormatOptions,
+    }
+
+    #[wasm_bindgen]
+    impl EncodeOptions {
+        #[wasm_bindgen(constructor)]
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        #[wasm_bindgen(getter, js_name = "format")]
+        pub fn get_format(&self) -> FormatOptions {
+            self.format.clone()
+        }
+
+        #[wasm_bindgen(setter)]
+        pub fn set_format(&mut self, format: FormatOptions) {
+            self.format = format;
+        }
+    }
+
+    /// Writes `mif` with `options`, taking the fill as a `T`.
+    fn encode<T: ToMif>(
+        mut mif: super::Mif<T>,
+        options: Option<EncodeOptions>,
+        fill: impl FnOnce(u64) -> T,
+    ) -> Result<String, JsError> {
+        let options = options.unwrap_or_default();
+
+        mif = mif
+            .with_encoding(options.encoding)
+            .with_format(options.format);
+
+        if let Some(width) = options.width {
+            mif = mif.with_width(width);
+        }
+
+        if let Some(depth) = options.depth {
+            mif = mif
+                .with_depth(depth)
+                .map_err(|kind| JsError::new(&kind.to_string()))?;
+        }
+
+        if let Some(value) = options.fill {
+            mif = mif
+                .with_fill(fill(value))
+                .map_err(|kind| JsError::new(&kind.to_string()))?;
+        }
+
+        Ok(mif.to_string())
+    }
+
     macro_rules! gen_writers {
         ($t:ty, $prefix:ident) => {
             paste! {
                 #[wasm_bindgen]
                 impl Mif {
                     #[wasm_bindgen(js_name = [<encode $prefix Array>])]
-                    pub fn [<encode $t>](data: &[$t], address_radix: Radix, data_radix: Radix) -> String {
-                        super::Mif::new(data, address_radix, data_radix).to_string()
+                    pub fn [<encode $t>](
+                        data: &[$t],
+                        address_radix: Radix,
+                        data_radix: Radix,
+                        options: Option<EncodeOptions>,
+                    ) -> Result<String, JsError> {
+                        encode(
+                            super::Mif::new(data, address_radix, data_radix),
+                            options,
+                            |fill| fill as $t,
+                        )
+                    }
+
+                    /// Writes `values[i]` at `addresses[i]`. Fails if the two
+                    /// differ in length.
+                    #[wasm_bindgen(js_name = [<encode $prefix Sparse>])]
+                    pub fn [<encode_sparse_ $t>](
+                        addresses: &[usize],
+                        values: &[$t],
+                        address_radix: Radix,
+                        data_radix: Radix,
+                        options: Option<EncodeOptions>,
+                    ) -> Result<String, JsError> {
+                        if addresses.len() != values.len() {
+                            return Err(JsError::new(
+                                "addresses and values differ in length",
+                            ));
+                        }
+
+                        let pairs = addresses.iter().copied().zip(values);
+
+                        encode(
+                            super::Mif::from_pairs(pairs, address_radix, data_radix),
+                            options,
+                            |fill| fill as $t,
+                        )
                     }
                 }
             }
@@ -353,3 +1091,492 @@ mod wasm {
     gen_writers!(u64, Uint64);
     gen_writers!(i64, Int64);
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        parser::{parse, parse_recovering_with},
+        Overlap, ParseOptions,
+    };
+
+    use super::*;
+
+    #[test]
+    pub fn widths() {
+        let mif = Mif::new(&[0xFFFFu16, 0x1234, 0x0234], Radix::Uns, Radix::Hex).with_width(12);
+
+        assert_eq!(
+            mif.to_string(),
+            "DEPTH=3;\nWIDTH=12;\nADDRESS_RADIX=UNS;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n0:FFF;\n[1..2]:234;\nEND;"
+        );
+        assert_eq!(
+            Mif::new(&[0x1000u16], Radix::Uns, Radix::Hex)
+                .try_with_width(12)
+                .err(),
+            Some(ErrorKind::ValueOutOfRange)
+        );
+
+        let mif = Mif::new(&[-8i16, 7, 200], Radix::Uns, Radix::Dec)
+            .try_with_width(4)
+            .map(|mif| mif.to_string());
+
+        assert_eq!(mif, Err(ErrorKind::ValueOutOfRange));
+
+        let mif = Mif::new(&[-8i16, 7, -1], Radix::Uns, Radix::Dec)
+            .try_with_width(4)
+            .unwrap()
+            .to_string();
+        let document = parse(&mif).unwrap();
+
+        assert!(mif.contains("0:-8;\n1:7;\n2:-1;\n"));
+        assert_eq!(document.width(), 4);
+        assert_eq!(document.get(0).unwrap().to_u64(), Some(0x8));
+        assert_eq!(document.get(2).unwrap().to_u64(), Some(0xF));
+        assert_eq!(
+            Mif::new(&[1i8], Radix::Uns, Radix::Dec)
+                .try_with_width(4)
+                .and_then(|mif| mif.with_fill(-9))
+                .err(),
+            Some(ErrorKind::ValueOutOfRange)
+        );
+        assert!(Mif::new(&[1i8], Radix::Uns, Radix::Dec)
+            .try_with_width(4)
+            .and_then(|mif| mif.with_fill(-8))
+            .is_ok());
+
+        let content = |mif: Mif<i8>| mif.to_string().split_once("BEGIN\n").unwrap().1.to_string();
+
+        assert_eq!(
+            content(Mif::new(&[-1i8, 5], Radix::Uns, Radix::Dec).with_width(16)),
+            "0:-1;\n1:5;\nEND;"
+        );
+        assert_eq!(
+            content(Mif::new(&[-1i8, 5], Radix::Uns, Radix::Hex).with_width(16)),
+            "0:FFFF;\n1:0005;\nEND;"
+        );
+        assert_eq!(
+            content(Mif::new(&[-2i8], Radix::Uns, Radix::Hex).with_width(72)),
+            "0:FFFFFFFFFFFFFFFFFE;\nEND;"
+        );
+    }
+
+    #[test]
+    pub fn default_limbs() {
+        struct Rgb565(u16);
+
+        impl ToMif for Rgb565 {
+            type SignedBinary = u16;
+            type Binary = u16;
+
+            const WIDTH: usize = 16;
+
+            fn signed(&self) -> u16 {
+                self.0
+            }
+
+            fn bits(&self) -> u16 {
+                self.0
+            }
+        }
+
+        assert_eq!(
+            Mif::new(&[Rgb565(0xF800), Rgb565(0x07E0)], Radix::Uns, Radix::Hex)
+                .to_string()
+                .split_once("BEGIN\n")
+                .unwrap()
+                .1,
+            "0:F800;\n1:07E0;\nEND;"
+        );
+    }
+
+    #[test]
+    pub fn default_is_negative() {
+        struct Fixed(i16);
+
+        impl ToMif for Fixed {
+            type SignedBinary = i16;
+            type Binary = u16;
+
+            const WIDTH: usize = 12;
+
+            fn signed(&self) -> i16 {
+                self.0
+            }
+
+            fn bits(&self) -> u16 {
+                self.0.cast_unsigned() & 0xFFF
+            }
+        }
+
+        assert_eq!(
+            Mif::new(&[Fixed(-3), Fixed(5)], Radix::Uns, Radix::Dec)
+                .to_string()
+                .split_once("BEGIN\n")
+                .unwrap()
+                .1,
+            "0:-3;\n1:5;\nEND;"
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn default_limbs_overflow() {
+        struct Wide(u16);
+
+        impl ToMif for Wide {
+            type SignedBinary = u16;
+            type Binary = u16;
+
+            const WIDTH: usize = 8;
+
+            fn signed(&self) -> u16 {
+                self.0
+            }
+
+            fn bits(&self) -> u16 {
+                self.0
+            }
+        }
+
+        Mif::new(&[Wide(0x1FF)], Radix::Uns, Radix::Hex).to_string();
+    }
+
+    #[test]
+    pub fn depths() {
+        let mif = Mif::new(&[1u8, 2, 0xFF], Radix::Hex, Radix::Hex)
+            .with_depth(16)
+            .unwrap()
+            .with_fill(0xFF)
+            .unwrap();
+
+        assert_eq!(
+            mif.to_string(),
+            "DEPTH=16;\nWIDTH=8;\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n0:01;\n1:02;\n[2..F]:FF;\nEND;"
+        );
+
+        let document = parse(
+            Mif::new(&[7u32; 3], Radix::Uns, Radix::Uns)
+                .with_width(17)
+                .with_depth(1000)
+                .unwrap()
+                .to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(document.depth(), 1000);
+        assert_eq!(document.get(2).unwrap().to_u64(), Some(7));
+        assert_eq!(document.get(999).unwrap().to_u64(), Some(0));
+        assert_eq!(
+            Mif::new(&[1u8, 2], Radix::Hex, Radix::Hex)
+                .with_depth(1)
+                .err(),
+            Some(ErrorKind::AddressOutOfRange)
+        );
+        assert!(Mif::new(&[] as &[u8], Radix::Hex, Radix::Hex)
+            .to_string()
+            .ends_with("CONTENT BEGIN\nEND;"));
+    }
+
+    #[test]
+    pub fn segments() {
+        let code = [0x10u8, 0x20, 0x20];
+        let data = [0xAAu8; 4];
+        let vectors = [0x01u8, 0x02];
+        let mif = Mif::from_segments(
+            &[(0x0000, &code[..]), (0x8000, &data), (0xFFF0, &vectors)],
+            Radix::Hex,
+            Radix::Hex,
+        )
+        .with_depth(0x10000)
+        .unwrap();
+
+        assert_eq!(
+            mif.to_string(),
+            "DEPTH=65536;\nWIDTH=8;\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\nCONTENT BEGIN\n0000:10;\n[0001..0002]:20;\n[8000..8003]:AA;\nFFF0:01;\nFFF1:02;\nEND;"
+        );
+        assert!(mif.with_fill(0xFF).unwrap().to_string().contains(
+            "[8000..8003]:AA;\n[8004..FFEF]:FF;\nFFF0:01;\nFFF1:02;\n[FFF2..FFFF]:FF;\n"
+        ));
+    }
+
+    #[test]
+    pub fn pairs() {
+        let mif = Mif::from_pairs([(5, -1i16), (1, 3), (5, 4), (2, 3)], Radix::Uns, Radix::Dec)
+            .with_fill(-2)
+            .unwrap();
+
+        assert_eq!(
+            mif.to_string(),
+            "DEPTH=6;\nWIDTH=16;\nADDRESS_RADIX=UNS;\nDATA_RADIX=DEC;\nCONTENT BEGIN\n0:-2;\n[1..2]:3;\n[3..4]:-2;\n5:4;\nEND;"
+        );
+
+        let document = parse(mif.to_string()).unwrap();
+
+        assert_eq!(document.get(3).unwrap().to_u64(), Some(0xFFFE));
+        assert_eq!(
+            Mif::from_pairs([(7, 1u8)], Radix::Hex, Radix::Hex)
+                .with_depth(7)
+                .err(),
+            Some(ErrorKind::AddressOutOfRange)
+        );
+    }
+
+    #[test]
+    pub fn smallest() {
+        let smallest = |data: &[u8]| {
+            Mif::new(data, Radix::Hex, Radix::Hex)
+                .with_encoding(Encoding::Smallest)
+                .to_string()
+        };
+        let overriding = |data: &[u8]| {
+            Mif::new(data, Radix::Hex, Radix::Hex)
+                .with_encoding(Encoding::SmallestOverriding)
+                .to_string()
+        };
+        let content = |mif: String| mif.split_once("BEGIN\n").unwrap().1.to_string();
+
+        assert_eq!(
+            content(smallest(&[1, 2, 3].repeat(10))),
+            "[00..1D]:01 02 03;\nEND;"
+        );
+        assert_eq!(content(smallest(&[1, 2, 3, 4])), "0:01 02 03 04;\nEND;");
+
+        // Decimal addresses aren't padded, so they are costed unpadded too.
+        let data = [1u8, 2, 3].repeat(40);
+        let decimal = Mif::new(&data, Radix::Uns, Radix::Hex).with_encoding(Encoding::Smallest);
+
+        assert_eq!(content(decimal.to_string()), "[0..119]:01 02 03;\nEND;");
+
+        let mut data = [0u8; 256];
+        data[0x10] = 5;
+        data[0x80] = 7;
+
+        assert_eq!(
+            content(smallest(&data)),
+            "[00..0F]:00;\n10:05;\n[11..7F]:00;\n80:07;\n[81..FF]:00;\nEND;"
+        );
+        assert_eq!(
+            content(overriding(&data)),
+            "[00..FF]:00;\n10:05;\n80:07;\nEND;"
+        );
+
+        let sparse = Mif::from_pairs(
+            [(0, 1u8), (1 << 40, 2), (1 << 40 | 2, 2)],
+            Radix::Hex,
+            Radix::Hex,
+        )
+        .with_encoding(Encoding::Smallest);
+
+        assert_eq!(
+            content(sparse.to_string()),
+            "00000000000:01;\n10000000000:02 00 02;\nEND;"
+        );
+    }
+
+    #[test]
+    pub fn smallest_round_trip() {
+        let mut seed = 0x2545_F491_4F6C_DD1Du64;
+        let mut random = |bound: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % bound
+        };
+
+        for case in 0..200 {
+            let width = 1 + random(20) as usize;
+            let depth = 1 + random(300) as usize;
+            let alphabet = 1 + random(6);
+            let period = 1 + random(5) as usize;
+            let pattern = (0..period)
+                .map(|_| random(alphabet) * 0x1111)
+                .collect::<Vec<_>>();
+            let mut pairs = Vec::new();
+
+            let hole = match case % 5 {
+                1 => depth / 4..depth * 3 / 4,
+                _ => 0..0,
+            };
+
+            for address in 0..depth {
+                if case % 3 == 0 && random(4) == 0 || hole.contains(&address) {
+                    continue;
+                }
+
+                let value = match random(8) {
+                    0 => random(1 << 20),
+                    _ => pattern[address % period],
+                };
+
+                pairs.push((address, value as u32));
+            }
+            let fill = (case % 2 == 0).then(|| random(alphabet) as u32);
+            let mut words = vec![fill.unwrap_or(0) as u64; depth];
+
+            for &(address, value) in &pairs {
+                words[address] = value as u64;
+            }
+
+            let writer = |encoding| {
+                let mif = Mif::from_pairs(pairs.iter().copied(), Radix::Uns, Radix::Hex)
+                    .with_width(width)
+                    .with_depth(depth)
+                    .unwrap()
+                    .with_encoding(encoding);
+
+                match fill {
+                    Some(fill) => mif.with_fill(fill).unwrap(),
+                    None => mif,
+                }
+                .to_string()
+            };
+            let overlap = ParseOptions {
+                overlap: Overlap::Error,
+                ..ParseOptions::default()
+            };
+            let text = writer(Encoding::Smallest);
+            let overriding = writer(Encoding::SmallestOverriding);
+            let (document, diagnostics) = parse_recovering_with(&text, &overlap);
+            let document = document.unwrap();
+
+            assert_eq!(diagnostics, [], "{text}");
+
+            for (document, text) in [
+                (document, &text),
+                (parse(&overriding).unwrap(), &overriding),
+            ] {
+                for (address, word) in words.iter().enumerate() {
+                    assert_eq!(
+                        document.get(address).unwrap().to_u64(),
+                        Some(word & ((1 << width) - 1)),
+                        "{text}"
+                    );
+                }
+            }
+
+            assert!(overriding.len() <= text.len(), "{overriding}");
+            assert!(text.len() <= writer(Encoding::Runs).len(), "{text}");
+        }
+    }
+
+    #[test]
+    pub fn words_per_line() {
+        let content = |mif: Mif<u8>| {
+            let mif = mif.to_string();
+
+            mif.split_once("BEGIN\n").unwrap().1.to_string()
+        };
+        let data = (0..20).collect::<Vec<u8>>();
+
+        assert_eq!(
+            content(Mif::new(&data, Radix::Hex, Radix::Hex).with_words_per_line(8)),
+            "00:00 01 02 03 04 05 06 07;\n08:08 09 0A 0B 0C 0D 0E 0F;\n10:10 11 12 13;\nEND;"
+        );
+
+        let compact = |mif: Mif<u8>| content(mif.with_format(FormatOptions::compact()));
+
+        assert_eq!(
+            compact(Mif::new(&data[..6], Radix::Hex, Radix::Hex).with_words_per_line(3)),
+            "0:0 1 2;\n3:3 4 5;\nEND;"
+        );
+        assert_eq!(
+            content(
+                Mif::new(&data[..6], Radix::Hex, Radix::Hex)
+                    .with_format(FormatOptions::compact())
+                    .with_words_per_line(3)
+            ),
+            "0:0 1 2;\n3:3 4 5;\nEND;"
+        );
+
+        let data = [vec![1, 2, 3], vec![0; 20], vec![4]].concat();
+
+        assert_eq!(
+            content(Mif::new(&data, Radix::Hex, Radix::Hex).with_words_per_line(4)),
+            "00:01 02 03 00;\n[04..13]:00;\n14:00 00 00 04;\nEND;"
+        );
+        assert_eq!(
+            content(
+                Mif::from_pairs([(0, 1), (1, 2), (5, 3), (6, 4)], Radix::Hex, Radix::Hex)
+                    .with_words_per_line(4)
+            ),
+            "0:01 02;\n5:03 04;\nEND;"
+        );
+
+        let document = parse(
+            Mif::new(&data, Radix::Uns, Radix::Uns)
+                .with_words_per_line(3)
+                .to_string(),
+        )
+        .unwrap();
+
+        for (address, &value) in data.iter().enumerate() {
+            assert_eq!(document.get(address).unwrap().to_u64(), Some(value as u64));
+        }
+    }
+
+    #[test]
+    pub fn formats() {
+        let data = [0xABu8, 0xAB, 0xAB, 0xAB, 0x0C];
+        let write = |format: FormatOptions| {
+            Mif::new(&data, Radix::Hex, Radix::Hex)
+                .with_depth(0x100)
+                .unwrap()
+                .with_format(format)
+                .to_string()
+        };
+        let content = |mif: String| mif.split_once("BEGIN").unwrap().1.to_string();
+
+        assert_eq!(
+            content(write(FormatOptions::default())),
+            "\n[00..03]:AB;\n04:0C;\n[05..FF]:00;\nEND;"
+        );
+        assert_eq!(
+            content(write(FormatOptions::quartus())),
+            "\n\t[00..03] : AB;\n\t04 : 0C;\n\t[05..FF] : 00;\nEND;"
+        );
+        assert_eq!(
+            content(write(FormatOptions::compact())),
+            "\n[0..3]:AB;\n4:C;\n[5..FF]:0;\nEND;"
+        );
+        assert_eq!(
+            content(write(FormatOptions {
+                uppercase: false,
+                crlf: true,
+                words_per_line: 4,
+                ..FormatOptions::compact()
+            })),
+            "\r\n[0..3]:ab;\r\n4:c 0 0 0;\r\n[8..ff]:0;\r\nEND;"
+        );
+
+        let text = write(FormatOptions::one_word_per_line());
+
+        assert!(text.contains("BEGIN\n00:AB;\n01:AB;\n02:AB;\n03:AB;\n04:0C;\n05:00;\n"));
+        assert_eq!(text.lines().count(), 5 + 0x100 + 1);
+
+        for format in [
+            FormatOptions::quartus(),
+            FormatOptions::compact(),
+            FormatOptions {
+                crlf: true,
+                uppercase: false,
+                ..FormatOptions::quartus()
+            },
+        ] {
+            for encoding in [
+                Encoding::Runs,
+                Encoding::Smallest,
+                Encoding::SmallestOverriding,
+            ] {
+                let text = Mif::new(&data, Radix::Hex, Radix::Hex)
+                    .with_encoding(encoding)
+                    .with_format(format.clone())
+                    .to_string();
+                let document = parse(&text).unwrap();
+
+                for (address, &value) in data.iter().enumerate() {
+                    assert_eq!(document.get(address).unwrap().to_u64(), Some(value as u64));
+                }
+            }
+        }
+    }
+}